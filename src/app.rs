@@ -1,4 +1,10 @@
-use clap::{Parser, Subcommand};
+use std::ffi::OsString;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use hed::format::{Align, Flavor};
+use hed::view::{GroupBy, Only, SortBy};
+use hed::Family;
 
 #[derive(Parser, Debug)]
 #[clap(name = env!("CARGO_PKG_NAME"))]
@@ -9,28 +15,119 @@ use clap::{Parser, Subcommand};
     long_about = "Host EDitor allows you to manipulate the /etc/hosts file. It will manage adding new hosts and removing old entries. Any entry added will be validated (valid ip, non-existing previous entry)."
 )]
 pub struct Cli {
-    /// Instead of /etc/hosts, use this file (testing)
-    #[clap(long, required = false, default_value = "/etc/hosts")]
-    pub file: String,
+    /// Instead of /etc/hosts, use this file (testing). With `-` the file is
+    /// read from stdin and the result is written to stdout
+    #[clap(long, required = false)]
+    pub file: Option<String>,
+    /// Use this configuration file instead of ~/.config/hed/config.toml
+    #[clap(long, required = false)]
+    pub config: Option<String>,
+    /// Fail instead of asking for privileges when the file is not writable
+    #[clap(long)]
+    pub no_escalate: bool,
+    /// Align the columns when writing the file: `none`, `file` or `section`
+    #[clap(long, value_name = "none|file|section")]
+    pub align: Option<Align>,
+    /// Follow the conventions of the hosts file of `unix` or `windows`: CRLF
+    /// line endings, a hostname per line and at most 9 names on a line
+    #[clap(long, value_name = "unix|windows")]
+    pub flavor: Option<Flavor>,
+    /// When to use colors: `auto` (when writing to a terminal and `NO_COLOR`
+    /// is not set), `always` or `never`
+    #[clap(long, value_name = "WHEN", value_enum, default_value = "auto")]
+    pub color: ColorChoice,
     #[clap(subcommand)]
     pub command: Commands,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output is colored, `tty` tells if it is written to a terminal
+    /// and `no_color` is the value of `NO_COLOR`
+    pub fn enabled(self, tty: bool, no_color: Option<OsString>) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => tty && no_color.iter().all(|v| v.is_empty()),
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Verify the integrity of the hosts file
     Verify {},
     /// List your current hostfile
-    Show {},
+    Show {
+        /// Sort the entries by `ip` or `name`
+        #[clap(long, value_name = "ip|name")]
+        sort: Option<SortBy>,
+        /// Group the entries by `subnet`, `domain` or `header-comment`
+        #[clap(long, value_name = "subnet|domain|header-comment")]
+        group_by: Option<GroupBy>,
+        /// Only show `ipv4`, `ipv6`, `comments` or `entries`
+        #[clap(long, value_name = "ipv4|ipv6|comments|entries")]
+        only: Option<Only>,
+    },
+    /// Open the hostfile in $VISUAL or $EDITOR, validating it before saving
+    Edit {},
+    /// Check that the system resolver returns the hostfile entry for a hostname
+    #[command(arg_required_else_help = true)]
+    Check {
+        /// Hostname to look up
+        #[clap(required = true, index = 1)]
+        hostname: String,
+    },
+    /// Show the names of an IP address or CIDR network, as a reverse lookup returns them
+    #[command(arg_required_else_help = true)]
+    Who {
+        /// IP address or network, like 10.10.10.0/24
+        #[clap(required = true, index = 1)]
+        address: String,
+    },
     /// Add a host to your hostfile
     #[command(arg_required_else_help = true)]
     Add {
         /// Hostname to add to the hostfile
         #[clap(required = true, index = 1)]
         hostname: String,
-        /// IP address of the host
+        /// IP address of the host, a link-local IPv6 address can have a zone:
+        /// fe80::1%eth0
         #[clap(required = false, index = 2)]
         ip: Option<String>,
+        /// IP address of the other family, with --dual-stack
+        #[clap(required = false, index = 3, requires = "dual_stack")]
+        other_ip: Option<String>,
+        /// Add the hostname for both an IPv4 and an IPv6 address
+        #[clap(long, requires = "other_ip", conflicts_with_all = ["force_first", "subdomains"])]
+        dual_stack: bool,
+        /// Add the mapping above any line that already has the hostname, so it
+        /// is the one resolvers use
+        #[clap(long, requires = "ip")]
+        force_first: bool,
+        /// Comma separated subdomains to add as aliases of the hostname
+        #[clap(long, value_delimiter = ',', conflicts_with = "force_first")]
+        subdomains: Vec<String>,
+        /// Exit with code 11 instead of 0 when everything is already in the
+        /// hostfile
+        #[clap(long)]
+        exit_unchanged: bool,
+    },
+    /// Add the subdomains listed in a wordlist as aliases of a domain
+    #[command(arg_required_else_help = true)]
+    Expand {
+        /// Domain that is already in the hostfile
+        #[clap(required = true, index = 1)]
+        domain: String,
+        /// File with a subdomain or hostname on each line
+        #[clap(required = true, index = 2)]
+        wordlist: String,
     },
     /// Replace the IP address for a hostname in your hostfile
     #[command(arg_required_else_help = true)]
@@ -41,6 +138,14 @@ pub enum Commands {
         /// IP address to change to
         #[clap(required = true, index = 2)]
         ip: Option<String>,
+        /// Replace the entry of this address family, `4` or `6`, instead of
+        /// the one of the new IP address
+        #[clap(long, value_name = "4|6")]
+        family: Option<Family>,
+        /// Only move this name to the new IP address, the other names on its
+        /// line keep the old one
+        #[clap(long)]
+        only_this_name: bool,
     },
     /// Alias a name to an existing hostname
     Alias {
@@ -51,10 +156,77 @@ pub enum Commands {
         #[clap(required = true, index = 2)]
         alias: String,
     },
+    /// Make an alias the canonical name of its line, the name reverse lookups
+    /// return
+    #[command(arg_required_else_help = true)]
+    Canonical {
+        /// Alias to make the canonical name
+        #[clap(required = true, index = 1)]
+        hostname: String,
+    },
+    /// Rename a hostname, keeping its position on every line it is on
+    #[command(arg_required_else_help = true)]
+    Rename {
+        /// Hostname to rename
+        #[clap(required = true, index = 1)]
+        old: String,
+        /// New hostname
+        #[clap(required = true, index = 2)]
+        new: String,
+    },
+    /// Move a hostname to another IP address, keeping the comments
+    #[command(arg_required_else_help = true)]
+    Move {
+        /// Hostname to move
+        #[clap(required = true, index = 1)]
+        hostname: String,
+        /// IP address to move it to
+        #[clap(required = true, index = 2)]
+        ip: String,
+    },
     /// Delete a host from your hostfile
     Delete {
         /// IP or hostname to remove
         #[clap(required = true, index = 1)]
         entry: String,
+        /// Only remove the hostname from the entries of this address family,
+        /// `4` or `6`; not for an IP address
+        #[clap(long, value_name = "4|6")]
+        family: Option<Family>,
+    },
+    /// Print the completion script for a shell
+    #[command(arg_required_else_help = true)]
+    Completions {
+        /// Shell to complete for
+        #[clap(required = true, index = 1, value_enum)]
+        shell: Shell,
     },
+    /// Print the man page
+    Man {},
+    /// Print the hostnames and IP addresses in the hostfile, used by the
+    /// completion scripts
+    #[command(hide = true)]
+    CompleteHosts {},
+    /// Replace the hostfile of the system wide configuration by the content
+    /// on stdin, used by hed itself to write the hostfile with elevated
+    /// privileges
+    #[command(hide = true)]
+    WriteHelper {},
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use crate::app::ColorChoice;
+
+    #[test]
+    fn test_color_choice() {
+        assert!(ColorChoice::Auto.enabled(true, None));
+        assert!(ColorChoice::Auto.enabled(true, Some(OsString::new())));
+        assert!(!ColorChoice::Auto.enabled(true, Some(OsString::from("1"))));
+        assert!(!ColorChoice::Auto.enabled(false, None));
+        assert!(ColorChoice::Always.enabled(false, Some(OsString::from("1"))));
+        assert!(!ColorChoice::Never.enabled(true, None));
+    }
 }
@@ -0,0 +1,267 @@
+use std::{
+    fmt, fs,
+    net::{IpAddr, ToSocketAddrs},
+    path::Path,
+};
+
+use hed::{ApplicationError, HostEntry, HostFile, Modifications, Shadowed};
+
+use crate::config::DEFAULT_FILE;
+
+/// Configuration of the name service switch, it lists the sources of names
+const NSSWITCH: &str = "/etc/nsswitch.conf";
+
+/// Compare the entries for `hostname` in the hostfile to the addresses the
+/// system resolver (getaddrinfo) returns for it, and explain any difference.
+pub fn check(hf: &HostFile, hostname: &str) -> Result<Modifications, ApplicationError> {
+    let entries = hf.lookup(hostname);
+    if entries.is_empty() {
+        println!("{} is not listed in {}", hostname, hf.filename);
+    }
+    for (line, entry) in &entries {
+        println!("{} line {}: {}", hf.filename, line, entry);
+    }
+
+    let resolved = match resolve(hostname) {
+        Ok(addrs) => {
+            let list: Vec<String> = addrs.iter().map(|a| a.to_string()).collect();
+            println!("system resolver: {}", list.join(" "));
+            addrs
+        }
+        Err(e) => {
+            println!("system resolver: {}", e);
+            vec![]
+        }
+    };
+
+    let sources = fs::read_to_string(NSSWITCH)
+        .ok()
+        .and_then(|c| hosts_sources(&c));
+    let shadowed: Vec<Shadowed> = hf
+        .shadowed()
+        .into_iter()
+        .filter(|s| s.name == hostname)
+        .collect();
+    let problems = explain(
+        is_system_hostfile(&hf.filename),
+        &entries,
+        &shadowed,
+        &resolved,
+        sources.as_deref(),
+    );
+
+    if entries.is_empty() {
+        return Err(ApplicationError::HostnameDoesNotExist(String::from(
+            hostname,
+        )));
+    }
+    if problems.is_empty() {
+        println!("The system resolves {} as listed in the hostfile", hostname);
+        return Ok(Modifications::new());
+    }
+    for problem in &problems {
+        println!("- {}", problem);
+    }
+    Err(ApplicationError::ResolutionMismatch(String::from(hostname)))
+}
+
+/// The addresses getaddrinfo returns for `hostname`, without duplicates
+fn resolve(hostname: &str) -> std::io::Result<Vec<IpAddr>> {
+    let mut addrs: Vec<IpAddr> = vec![];
+    for addr in (hostname, 0).to_socket_addrs()? {
+        if !addrs.contains(&addr.ip()) {
+            addrs.push(addr.ip());
+        }
+    }
+    Ok(addrs)
+}
+
+fn is_system_hostfile(filename: &str) -> bool {
+    match (fs::canonicalize(filename), fs::canonicalize(DEFAULT_FILE)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => Path::new(filename) == Path::new(DEFAULT_FILE),
+    }
+}
+
+/// The sources of the `hosts` database in nsswitch.conf, without the
+/// `[STATUS=action]` items. `None` when there is no `hosts` line.
+fn hosts_sources(nsswitch: &str) -> Option<Vec<String>> {
+    nsswitch
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .find_map(|l| l.strip_prefix("hosts:"))
+        .map(|sources| {
+            sources
+                .split_whitespace()
+                .filter(|s| !s.starts_with('[') && !s.ends_with(']'))
+                .map(String::from)
+                .collect()
+        })
+}
+
+/// A reason the system resolver does not return what the hostfile lists
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    /// The hostfile is not the one the system resolver reads
+    OtherFile,
+    /// nsswitch.conf does not list `files` for hosts
+    NoFiles,
+    /// The source that nsswitch.conf asks before `files`
+    AskedFirst(String),
+    /// A line for the hostname that an earlier line of its family shadows
+    Shadowed(Shadowed),
+    /// An address of the hostfile the resolver does not return
+    Missing(IpAddr),
+    /// An address the resolver returns that is not in the hostfile
+    Unexpected(IpAddr),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::OtherFile => write!(
+                f,
+                "the system resolver reads {}, not the file given with --file",
+                DEFAULT_FILE
+            ),
+            Problem::NoFiles => write!(
+                f,
+                "nsswitch.conf does not list `files` for hosts, the hosts file is not used"
+            ),
+            Problem::AskedFirst(first) if first == "resolve" => write!(f, "systemd-resolved (`resolve`) is asked before `files` in nsswitch.conf, it can answer from its cache; try `resolvectl flush-caches`"),
+            Problem::AskedFirst(first) => write!(
+                f,
+                "`{}` is asked before `files` in nsswitch.conf and can answer first",
+                first
+            ),
+            Problem::Shadowed(s) => write!(
+                f,
+                "line {} is shadowed by line {}, the first line for {} wins and maps it to {}",
+                s.line,
+                s.by_line,
+                if s.ip.is_ipv4() { "IPv4" } else { "IPv6" },
+                s.by_ip
+            ),
+            Problem::Missing(ip) => write!(f, "the resolver does not return {}", ip),
+            Problem::Unexpected(ip) => write!(
+                f,
+                "the resolver returns {}, which does not come from the hostfile (DNS?)",
+                ip
+            ),
+        }
+    }
+}
+
+/// Explain why the resolver does not return what the hostfile lists. Only
+/// the first line for each address family is used by most resolvers, the
+/// other lines of `entries` are in `shadowed`.
+fn explain(
+    system_hostfile: bool,
+    entries: &[(usize, &HostEntry)],
+    shadowed: &[Shadowed],
+    resolved: &[IpAddr],
+    sources: Option<&[String]>,
+) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    if !system_hostfile {
+        problems.push(Problem::OtherFile);
+    }
+
+    match sources {
+        Some(s) if !s.iter().any(|x| x == "files") => problems.push(Problem::NoFiles),
+        Some(s) if s[0] != "files" => problems.push(Problem::AskedFirst(s[0].clone())),
+        _ => {}
+    }
+
+    problems.extend(shadowed.iter().cloned().map(Problem::Shadowed));
+    let expected: Vec<IpAddr> = entries
+        .iter()
+        .filter(|(line, _)| !shadowed.iter().any(|s| s.line == *line))
+        .filter_map(|(_, e)| e.ip)
+        .collect();
+
+    for ip in &expected {
+        if !resolved.contains(ip) {
+            problems.push(Problem::Missing(*ip));
+        }
+    }
+    for ip in resolved {
+        if !expected.contains(ip) && !entries.iter().any(|(_, e)| e.has_ip(ip)) {
+            problems.push(Problem::Unexpected(*ip));
+        }
+    }
+
+    // with a matching answer the order of nsswitch.conf did no harm
+    let answered = !expected.is_empty()
+        && expected.iter().all(|ip| resolved.contains(ip))
+        && resolved.iter().all(|ip| expected.contains(ip));
+    if answered && system_hostfile {
+        problems.retain(|p| matches!(p, Problem::Shadowed(_)));
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use hed::{HostEntry, HostFile};
+
+    use crate::check::{explain, hosts_sources, Problem};
+
+    #[test]
+    fn test_hosts_sources() {
+        let conf = "# comment\npasswd: files\nhosts:  files mdns4_minimal [NOTFOUND=return] dns # dns last\n";
+        assert_eq!(
+            hosts_sources(conf).unwrap(),
+            vec!["files", "mdns4_minimal", "dns"]
+        );
+        assert_eq!(hosts_sources("passwd: files\n"), None);
+    }
+
+    #[test]
+    fn test_explain() {
+        let first: HostEntry = "10.10.10.10 box.htb".parse().unwrap();
+        let second: HostEntry = "10.10.10.11 box.htb".parse().unwrap();
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let files = vec![String::from("files"), String::from("dns")];
+
+        let entries = vec![(2, &first)];
+        assert!(explain(true, &entries, &[], &[ip("10.10.10.10")], Some(&files)).is_empty());
+
+        let problems = explain(true, &entries, &[], &[ip("1.2.3.4")], Some(&files));
+        assert_eq!(
+            problems,
+            vec![
+                Problem::Missing(ip("10.10.10.10")),
+                Problem::Unexpected(ip("1.2.3.4"))
+            ]
+        );
+
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from("# lab\n10.10.10.10 box.htb\n\n\n10.10.10.11 box.htb\n".as_bytes())
+            .unwrap();
+        let shadowed = hf.shadowed();
+        let entries = vec![(2, &first), (5, &second)];
+        let problems = explain(
+            true,
+            &entries,
+            &shadowed,
+            &[ip("10.10.10.10")],
+            Some(&files),
+        );
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0]
+            .to_string()
+            .starts_with("line 5 is shadowed by line 2"));
+
+        let dns = vec![String::from("dns")];
+        let problems = explain(true, &[(2, &first)], &[], &[ip("1.2.3.4")], Some(&dns));
+        assert_eq!(problems[0], Problem::NoFiles);
+
+        let resolve = vec![String::from("resolve"), String::from("files")];
+        let problems = explain(true, &[(2, &first)], &[], &[ip("1.2.3.4")], Some(&resolve));
+        assert!(problems[0].to_string().contains("systemd-resolved"));
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::Write;
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+use hed::{ApplicationError, HostFile, Modifications};
+
+use crate::app::Cli;
+
+/// Bash completion of the hostnames and IP addresses after `delete`,
+/// `replace`, `alias`, `canonical`, `rename`, `move`, `check` and `who`, the
+/// options before the command are passed on so the same hostfile is read.
+/// `complete-hosts` refuses to read stdin, so `--file -` does not block.
+const BASH_DYNAMIC: &str = r#"
+_hed_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    case "${prev}" in
+        delete|replace|alias|canonical|rename|move|check|who)
+            COMPREPLY=( $(compgen -W "$("${COMP_WORDS[0]}" "${COMP_WORDS[@]:1:COMP_CWORD-2}" complete-hosts 2>/dev/null)" -- "${cur}") )
+            ;;
+        *)
+            _hed "$@"
+            ;;
+    esac
+}
+complete -F _hed_dynamic -o bashdefault -o default hed
+"#;
+
+const ZSH_DYNAMIC: &str = r#"
+_hed_dynamic() {
+    case "${words[CURRENT-1]}" in
+        delete|replace|alias|canonical|rename|move|check|who)
+            compadd -- ${(f)"$(${words[1]} ${words[2,CURRENT-2]} complete-hosts 2>/dev/null)"}
+            ;;
+        *)
+            _hed "$@"
+            ;;
+    esac
+}
+compdef _hed_dynamic hed
+"#;
+
+const FISH_DYNAMIC: &str = r#"
+function __hed_complete_hosts
+    set -l tokens (commandline -opc)
+    set -l options $tokens[2..-1]
+    set -e options[-1]
+    $tokens[1] $options complete-hosts 2>/dev/null
+end
+complete -c hed -f -n 'contains -- (commandline -opc)[-1] delete replace alias canonical rename move check who' -a '(__hed_complete_hosts)'
+"#;
+
+/// Write the completion script for `shell` to `out`. For bash, zsh and fish
+/// it completes the hostnames and IP addresses in the hostfile as well.
+pub fn generate<W: Write>(shell: Shell, out: &mut W) -> Result<(), ApplicationError> {
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(shell, &mut cmd, name, out);
+
+    let dynamic = match shell {
+        Shell::Bash => BASH_DYNAMIC,
+        Shell::Zsh => ZSH_DYNAMIC,
+        Shell::Fish => FISH_DYNAMIC,
+        _ => "",
+    };
+    out.write_all(dynamic.as_bytes())?;
+    Ok(())
+}
+
+/// Write the hostnames and IP addresses in the hostfile to `out`, one per
+/// line, for the completion scripts
+pub fn hosts<W: Write>(hf: &HostFile, out: &mut W) -> Result<Modifications, ApplicationError> {
+    let mut seen: Vec<String> = vec![];
+    for entry in hf.entries.iter().flatten() {
+        let ip = match entry.address() {
+            Some(ip) => ip,
+            None => continue,
+        };
+        for candidate in entry.names().into_iter().map(String::from).chain([ip]) {
+            if !seen.contains(&candidate) {
+                writeln!(out, "{}", candidate)?;
+                seen.push(candidate);
+            }
+        }
+    }
+    Ok(Modifications::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap_complete::Shell;
+    use hed::HostFile;
+
+    use crate::completions::{generate, hosts};
+
+    #[test]
+    fn test_generate() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut out = vec![];
+            generate(shell, &mut out).unwrap();
+            let script = String::from_utf8(out).unwrap();
+            assert!(script.contains("complete-hosts"));
+            assert!(script.contains("expand"));
+        }
+    }
+
+    #[test]
+    fn test_hosts() {
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from(
+            "# lab\n10.10.10.10 box.htb www.box.htb\n::1 localhost\n127.0.0.1 localhost\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut out = vec![];
+        hosts(&hf, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "box.htb\nwww.box.htb\n10.10.10.10\nlocalhost\n::1\n127.0.0.1\n"
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use hed::{
+    format::{Align, Flavor, LineEnding},
+    ApplicationError, HostFile, Theme,
+};
+use serde::Deserialize;
+
+use crate::escalate::Escalation;
+
+/// The hosts file that is managed when no other file is configured
+pub const DEFAULT_FILE: &str = "/etc/hosts";
+
+/// The system wide configuration file
+const SYSTEM_CONFIG: &str = "/etc/hed.toml";
+
+/// Settings read from `/etc/hed.toml` and the configuration file of the user
+/// (`$XDG_CONFIG_HOME/hed/config.toml`). Settings of the user override the
+/// system wide ones and `HED_*` environment variables override both.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Hosts file to manage (`HED_FILE`)
+    pub file: Option<String>,
+    /// Directory to store backups in (`HED_BACKUP_DIR`)
+    pub backup_dir: Option<String>,
+    /// Number of backups to keep (`HED_BACKUP_RETENTION`)
+    pub backup_retention: Option<usize>,
+    /// Header comment of the section new entries are added to (`HED_SECTION`)
+    pub section: Option<String>,
+    /// Treat validation warnings as errors (`HED_STRICT`)
+    pub strict: Option<bool>,
+    /// How privileges are obtained (`HED_ESCALATION`)
+    pub escalation: Option<Escalation>,
+    /// How the columns are aligned when writing (`HED_ALIGN`)
+    pub align: Option<Align>,
+    /// Maximum number of names on a line (`HED_MAX_NAMES`)
+    pub max_names: Option<usize>,
+    /// Maximum number of characters on a line (`HED_MAX_LINE_LENGTH`)
+    pub max_line_length: Option<usize>,
+    /// Conventions of the operating system the hostfile is for (`HED_FLAVOR`)
+    pub flavor: Option<Flavor>,
+    /// Line ending to write, the one of the file when unset
+    /// (`HED_LINE_ENDING`)
+    pub line_ending: Option<LineEnding>,
+    /// End the file with a line ending, as the file does when unset
+    /// (`HED_FINAL_NEWLINE`)
+    pub final_newline: Option<bool>,
+    pub theme: ThemeConfig,
+}
+
+/// Colors for `show`, see `Theme::color` for the accepted values
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub ip: Option<String>,
+    pub name: Option<String>,
+    pub alias: Option<String>,
+    pub comment: Option<String>,
+}
+
+impl Config {
+    /// Load the system and user configuration and apply the environment.
+    /// With `path` (or `HED_CONFIG`) that file is used as the user
+    /// configuration, it has to exist.
+    pub fn load(path: Option<&str>) -> Result<Config, ApplicationError> {
+        let mut config = Config::system()?;
+
+        match path
+            .map(String::from)
+            .or_else(|| env::var("HED_CONFIG").ok())
+        {
+            Some(p) => config.merge(Config::read(Path::new(&p))?),
+            None => {
+                if let Some(user) = Config::user_path() {
+                    if user.exists() {
+                        config.merge(Config::read(&user)?);
+                    }
+                }
+            }
+        }
+
+        config.merge(Config::from_env(|k| env::var(k).ok())?);
+        Ok(config)
+    }
+
+    /// The system wide configuration only, without the configuration of the
+    /// user and the environment. The write helper trusts nothing else.
+    pub fn system() -> Result<Config, ApplicationError> {
+        let mut config = Config::default();
+        let system = Path::new(SYSTEM_CONFIG);
+        if system.exists() {
+            config.merge(Config::read(system)?);
+        }
+        Ok(config)
+    }
+
+    /// `$XDG_CONFIG_HOME/hed/config.toml`, or `~/.config/hed/config.toml`
+    fn user_path() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("hed").join("config.toml"))
+    }
+
+    fn read(path: &Path) -> Result<Config, ApplicationError> {
+        let invalid =
+            |e: String| ApplicationError::ConfigNotParseable(format!("{}: {}", path.display(), e));
+
+        let content = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        Config::parse(&content).map_err(|e| invalid(e.to_string()))
+    }
+
+    fn parse(content: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    /// The settings from the `HED_*` variables returned by `var`
+    fn from_env<F: Fn(&str) -> Option<String>>(var: F) -> Result<Config, ApplicationError> {
+        let invalid = |key: &str, value: &str| {
+            ApplicationError::ConfigNotParseable(format!("{}={} is not valid", key, value))
+        };
+
+        let number = |key: &str| match var(key) {
+            Some(v) => v.parse().map(Some).map_err(|_| invalid(key, &v)),
+            None => Ok(None),
+        };
+        let backup_retention = number("HED_BACKUP_RETENTION")?;
+        let max_names = number("HED_MAX_NAMES")?;
+        let max_line_length = number("HED_MAX_LINE_LENGTH")?;
+        let flag = |key: &str| match var(key) {
+            Some(v) => match v.as_str() {
+                "1" | "true" | "yes" => Ok(Some(true)),
+                "0" | "false" | "no" => Ok(Some(false)),
+                _ => Err(invalid(key, &v)),
+            },
+            None => Ok(None),
+        };
+        let strict = flag("HED_STRICT")?;
+        let final_newline = flag("HED_FINAL_NEWLINE")?;
+        let escalation = match var("HED_ESCALATION") {
+            Some(v) => Some(v.parse()?),
+            None => None,
+        };
+        let align = match var("HED_ALIGN") {
+            Some(v) => Some(v.parse().map_err(|e: ApplicationError| {
+                ApplicationError::ConfigNotParseable(e.to_string())
+            })?),
+            None => None,
+        };
+        let flavor = match var("HED_FLAVOR") {
+            Some(v) => Some(v.parse().map_err(|e: ApplicationError| {
+                ApplicationError::ConfigNotParseable(e.to_string())
+            })?),
+            None => None,
+        };
+        let line_ending = match var("HED_LINE_ENDING") {
+            Some(v) => Some(v.parse().map_err(|e: ApplicationError| {
+                ApplicationError::ConfigNotParseable(e.to_string())
+            })?),
+            None => None,
+        };
+
+        Ok(Config {
+            file: var("HED_FILE"),
+            backup_dir: var("HED_BACKUP_DIR"),
+            backup_retention,
+            section: var("HED_SECTION"),
+            strict,
+            escalation,
+            align,
+            max_names,
+            max_line_length,
+            flavor,
+            line_ending,
+            final_newline,
+            theme: ThemeConfig::default(),
+        })
+    }
+
+    /// Override the settings with the ones that are set in `other`
+    fn merge(&mut self, other: Config) {
+        self.file = other.file.or_else(|| self.file.take());
+        self.backup_dir = other.backup_dir.or_else(|| self.backup_dir.take());
+        self.backup_retention = other.backup_retention.or(self.backup_retention);
+        self.section = other.section.or_else(|| self.section.take());
+        self.strict = other.strict.or(self.strict);
+        self.escalation = other.escalation.or(self.escalation);
+        self.align = other.align.or(self.align);
+        self.max_names = other.max_names.or(self.max_names);
+        self.max_line_length = other.max_line_length.or(self.max_line_length);
+        self.flavor = other.flavor.or(self.flavor);
+        self.line_ending = other.line_ending.or(self.line_ending);
+        self.final_newline = other.final_newline.or(self.final_newline);
+        self.theme.ip = other.theme.ip.or_else(|| self.theme.ip.take());
+        self.theme.name = other.theme.name.or_else(|| self.theme.name.take());
+        self.theme.alias = other.theme.alias.or_else(|| self.theme.alias.take());
+        self.theme.comment = other.theme.comment.or_else(|| self.theme.comment.take());
+    }
+
+    /// The hostfile to manage, `cli_file` when it is given
+    pub fn hostfile(&self, cli_file: Option<String>) -> HostFile {
+        let filename = cli_file
+            .or_else(|| self.file.clone())
+            .unwrap_or_else(|| String::from(DEFAULT_FILE));
+
+        let mut hf = HostFile::new(filename);
+        hf.backup_dir = self.backup_dir.as_ref().map(PathBuf::from);
+        hf.backup_retention = self.backup_retention.unwrap_or(1);
+        hf.section = self.section.clone();
+        hf.align = self.align.unwrap_or_default();
+        hf.max_names = self.max_names;
+        hf.max_line_length = self.max_line_length;
+        hf.flavor = self.flavor.unwrap_or_default();
+        hf.line_ending = self.line_ending;
+        hf.final_newline = self.final_newline;
+        hf
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict.unwrap_or(false)
+    }
+
+    pub fn escalation(&self) -> Escalation {
+        self.escalation.unwrap_or(Escalation::Sudo)
+    }
+
+    /// The default theme with the configured colors applied
+    pub fn theme(&self) -> Result<Theme, ApplicationError> {
+        let mut theme = Theme::default();
+        let colors = [
+            (&self.theme.ip, &mut theme.ip),
+            (&self.theme.name, &mut theme.name),
+            (&self.theme.alias, &mut theme.alias),
+            (&self.theme.comment, &mut theme.comment),
+        ];
+        for (configured, color) in colors {
+            if let Some(c) = configured {
+                *color = Theme::color(c).ok_or_else(|| {
+                    ApplicationError::ConfigNotParseable(format!("unknown color `{}`", c))
+                })?;
+            }
+        }
+        Ok(theme)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use hed::format::{Align, Flavor, LineEnding};
+
+    use crate::config::Config;
+    use crate::escalate::Escalation;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+file = "/etc/hosts.d/lab"
+backup_dir = "/var/backups/hed"
+backup_retention = 5
+section = "Active"
+strict = true
+escalation = "none"
+align = "section"
+max_names = 8
+flavor = "windows"
+line_ending = "crlf"
+final_newline = false
+
+[theme]
+ip = "yellow"
+"#,
+        )
+        .expect("Should parse");
+
+        assert_eq!(config.file.as_deref(), Some("/etc/hosts.d/lab"));
+        assert_eq!(config.backup_retention, Some(5));
+        assert_eq!(config.escalation(), Escalation::None);
+        assert!(config.strict());
+        assert!(config.theme().is_ok());
+
+        let hf = config.hostfile(None);
+        assert_eq!(hf.filename, "/etc/hosts.d/lab");
+        assert_eq!(hf.backup_dir, Some(PathBuf::from("/var/backups/hed")));
+        assert_eq!(hf.backup_retention, 5);
+        assert_eq!(hf.section.as_deref(), Some("Active"));
+        assert_eq!(hf.align, Align::Section);
+        assert_eq!(hf.max_names, Some(8));
+        assert_eq!(hf.flavor, Flavor::Windows);
+        assert_eq!(hf.line_ending, Some(LineEnding::Crlf));
+        assert_eq!(hf.final_newline, Some(false));
+
+        assert_eq!(
+            config.hostfile(Some(String::from("hosts"))).filename,
+            "hosts"
+        );
+
+        assert!(Config::parse("unknown = 1").is_err());
+        assert!(Config::parse("[theme]\nip = \"purple\"")
+            .unwrap()
+            .theme()
+            .is_err());
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::default();
+        assert_eq!(config.hostfile(None).filename, "/etc/hosts");
+        assert_eq!(config.hostfile(None).backup_retention, 1);
+        assert!(!config.strict());
+        assert_eq!(config.escalation(), Escalation::Sudo);
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut config = Config::parse("file = \"/etc/hosts.lab\"\nstrict = true").unwrap();
+        let env = Config::from_env(|k| match k {
+            "HED_FILE" => Some(String::from("/tmp/hosts")),
+            "HED_BACKUP_RETENTION" => Some(String::from("3")),
+            "HED_FINAL_NEWLINE" => Some(String::from("no")),
+            "HED_MAX_LINE_LENGTH" => Some(String::from("255")),
+            _ => None,
+        })
+        .unwrap();
+        config.merge(env);
+
+        assert_eq!(config.file.as_deref(), Some("/tmp/hosts"));
+        assert_eq!(config.backup_retention, Some(3));
+        assert_eq!(config.final_newline, Some(false));
+        assert_eq!(config.max_line_length, Some(255));
+        assert!(config.strict());
+
+        assert!(Config::from_env(|k| match k {
+            "HED_STRICT" => Some(String::from("maybe")),
+            _ => None,
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    env,
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    process::{self, Command},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hed::{ApplicationError, HostEntry, HostFile, Issue, Modifications, Severity};
+
+/// Lines starting with this marker are annotations added by `hed`, they are
+/// removed before the edited file is parsed.
+const ANNOTATION: &str = "#! hed: ";
+
+/// Open a copy of the hostfile in the editor of the user, just like `visudo`.
+///
+/// The result is parsed and validated, any errors (and warnings when `strict`)
+/// are annotated in the file and the user can edit it again or give up. The
+/// entries of `hf` are only replaced when the edited file validates.
+///
+/// The editor runs as the invoking user, only writing the result escalates.
+/// The copy is kept in a directory only that user can enter.
+pub fn edit(hf: &mut HostFile, strict: bool) -> Result<Modifications, ApplicationError> {
+    if hf.is_stdio() {
+        return Err(ApplicationError::EditorFailed(String::from(
+            "stdin can not be edited, use --file with a path",
+        )));
+    }
+    let content = fs::read(&hf.filename)
+        .map_err(|e| ApplicationError::HostFileUnreadable(format!("{}: {}", hf.filename, e)))?;
+
+    let dir = private_dir()?;
+    let res = match fs::metadata(&dir) {
+        // the directory is owned by the effective user
+        Ok(m) if m.uid() == 0 && env::var_os("SUDO_UID").is_some() => {
+            Err(ApplicationError::EditorFailed(String::from(
+                "refusing to run the editor as root, run `hed edit` without sudo, the hosts file is written with the `escalation` setting",
+            )))
+        }
+        _ => create_copy(&dir, &content).and_then(|tmp| edit_loop(hf, &tmp, strict)),
+    };
+    let _ = fs::remove_dir_all(&dir);
+    res
+}
+
+fn edit_loop(
+    hf: &mut HostFile,
+    tmp: &Path,
+    strict: bool,
+) -> Result<Modifications, ApplicationError> {
+    loop {
+        run_editor(tmp)?;
+
+        let content = fs::read(tmp)?;
+        let lines = strip_annotations(&content);
+        fs::write(tmp, [lines.join(&b'\n'), vec![b'\n']].concat())?;
+
+        let mut edited = HostFile::new(tmp.display().to_string());
+        edited.flavor = hf.flavor;
+        edited.max_names = hf.max_names;
+        edited.max_line_length = hf.max_line_length;
+        edited.parse()?;
+
+        let mut issues = edited.validate();
+        issues.extend(unrecognised_lines(&lines, &edited));
+        issues.sort_by_key(|i| i.line);
+
+        if !issues
+            .iter()
+            .any(|i| strict || i.severity == Severity::Error)
+        {
+            let mods = count_modifications(hf, &edited);
+            hf.take_entries(edited);
+            return Ok(mods);
+        }
+
+        for issue in &issues {
+            eprintln!("{}", issue);
+        }
+        fs::write(tmp, annotate(&lines, &issues))?;
+
+        if !ask_edit_again()? {
+            return Err(ApplicationError::EditAborted());
+        }
+    }
+}
+
+/// Create a new directory in the temporary directory that only the user can
+/// enter, like `mkdtemp`. An existing path is never reused.
+fn private_dir() -> Result<PathBuf, ApplicationError> {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let mut last = None;
+    for attempt in 0..100u32 {
+        let dir = env::temp_dir().join(format!(
+            "hed-{}-{:08x}",
+            process::id(),
+            seed.wrapping_add(attempt)
+        ));
+        match DirBuilder::new().mode(0o700).create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last = Some(e),
+            Err(e) => return Err(ApplicationError::EditorFailed(e.to_string())),
+        }
+    }
+    Err(ApplicationError::EditorFailed(format!(
+        "no temporary directory could be created: {}",
+        last.map(|e| e.to_string()).unwrap_or_default()
+    )))
+}
+
+/// Write the content to a new file in `dir`, readable only by the user
+fn create_copy(dir: &Path, content: &[u8]) -> Result<PathBuf, ApplicationError> {
+    let tmp = dir.join("hosts");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp)
+        .map_err(|e| ApplicationError::EditorFailed(format!("{}: {}", tmp.display(), e)))?;
+    file.write_all(content)?;
+    Ok(tmp)
+}
+
+/// Start `$VISUAL`, `$EDITOR` or `vi` on the file and wait for it to exit
+fn run_editor(file: &Path) -> Result<(), ApplicationError> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    match Command::new(program).args(parts).arg(file).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(ApplicationError::EditorFailed(format!(
+            "{} exited with {}",
+            editor, status
+        ))),
+        Err(e) => Err(ApplicationError::EditorFailed(format!("{}: {}", editor, e))),
+    }
+}
+
+fn ask_edit_again() -> Result<bool, ApplicationError> {
+    loop {
+        print!("What now? (e)dit again or (q)uit without saving: ");
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(false);
+        }
+        match answer.trim() {
+            "e" | "E" | "" => return Ok(true),
+            "q" | "Q" => return Ok(false),
+            _ => continue,
+        }
+    }
+}
+
+/// Remove the annotations of a previous round from the content. The lines
+/// are kept as bytes, they do not have to be valid UTF-8.
+fn strip_annotations(content: &[u8]) -> Vec<&[u8]> {
+    let content = content.strip_suffix(b"\n").unwrap_or(content);
+    if content.is_empty() {
+        return vec![];
+    }
+    content
+        .split(|b| *b == b'\n')
+        .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+        .filter(|l| !l.starts_with(ANNOTATION.as_bytes()))
+        .collect()
+}
+
+/// Lines that have content, but did not result in an entry or comment
+fn unrecognised_lines(lines: &[&[u8]], edited: &HostFile) -> Vec<Issue> {
+    let empty = HostEntry::empty();
+    edited
+        .line_numbers()
+        .into_iter()
+        .zip(edited.entries.iter().flatten())
+        .filter(|(line, entry)| {
+            **entry == empty
+                && matches!(lines.get(line - 1), Some(l) if !String::from_utf8_lossy(l).trim().is_empty())
+        })
+        .map(|(line, _)| {
+            Issue::error(
+                line,
+                String::from("not a comment or an `<ip> <hostname>` entry"),
+            )
+        })
+        .collect()
+}
+
+/// Put every issue as an annotation above the line it was found on
+fn annotate(lines: &[&[u8]], issues: &[Issue]) -> Vec<u8> {
+    let mut out = vec![];
+    for (idx, line) in lines.iter().enumerate() {
+        for issue in issues.iter().filter(|i| i.line == idx + 1) {
+            out.extend(format!("{}{}\n", ANNOTATION, issue).into_bytes());
+        }
+        out.extend_from_slice(line);
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Count the entries that were added and removed while editing. When only the
+/// order changed it counts as a single update.
+fn count_modifications(original: &HostFile, edited: &HostFile) -> Modifications {
+    let mut mods = Modifications::new();
+    let empty = HostEntry::empty();
+    let old: Vec<&HostEntry> = original.entries.iter().flatten().collect();
+    let new: Vec<&HostEntry> = edited.entries.iter().flatten().collect();
+
+    let mut pool: Vec<&HostEntry> = old.iter().filter(|e| ***e != empty).copied().collect();
+    for entry in new.iter().filter(|e| ***e != empty) {
+        match pool.iter().position(|e| e == entry) {
+            Some(p) => {
+                pool.remove(p);
+            }
+            None => mods.added_entries += 1,
+        }
+    }
+    mods.removed_entries += pool.len() as i32;
+
+    if mods == Modifications::new() && old != new {
+        mods.updated_entries += 1;
+    }
+    mods
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::editor::{
+        annotate, count_modifications, create_copy, private_dir, strip_annotations,
+        unrecognised_lines,
+    };
+    use hed::{HostFile, Issue, Modifications};
+
+    fn hostfile(lines: &[&str]) -> HostFile {
+        let mut hf = HostFile::new("/tmp/test");
+        hf.entries = Some(lines.iter().map(|l| l.parse().unwrap()).collect());
+        hf
+    }
+
+    #[test]
+    fn test_annotations_roundtrip() {
+        let lines: Vec<&[u8]> = vec![b"127.0.0.1 localhost", b"10.10.10.300 box.htb # caf\xe9"];
+        let issues = vec![Issue::error(2, String::from("bad ip"))];
+
+        let annotated = annotate(&lines, &issues);
+        assert_eq!(
+            annotated,
+            b"127.0.0.1 localhost\n#! hed: line 2: error: bad ip\n10.10.10.300 box.htb # caf\xe9\n"
+        );
+        assert_eq!(strip_annotations(&annotated), lines);
+        assert!(strip_annotations(b"").is_empty());
+    }
+
+    #[test]
+    fn test_unrecognised_lines() {
+        let text = vec!["127.0.0.1 localhost", "", "garbage"];
+        let lines: Vec<&[u8]> = text.iter().map(|l| l.as_bytes()).collect();
+        let issues = unrecognised_lines(&lines, &hostfile(&text));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 3);
+    }
+
+    #[test]
+    fn test_count_modifications() {
+        let original = hostfile(&["127.0.0.1 localhost", "10.10.10.10 box.htb"]);
+
+        let same = hostfile(&["127.0.0.1 localhost", "10.10.10.10 box.htb"]);
+        assert_eq!(count_modifications(&original, &same), Modifications::new());
+
+        let edited = hostfile(&["127.0.0.1 localhost", "", "10.10.10.11 box.htb"]);
+        let mods = count_modifications(&original, &edited);
+        assert_eq!(mods.added_entries, 1);
+        assert_eq!(mods.removed_entries, 1);
+
+        let reordered = hostfile(&["10.10.10.10 box.htb", "127.0.0.1 localhost"]);
+        assert_eq!(
+            count_modifications(&original, &reordered).updated_entries,
+            1
+        );
+    }
+
+    #[test]
+    fn test_private_copy() {
+        let dir = private_dir().unwrap();
+        let other = private_dir().unwrap();
+        assert_ne!(dir, other);
+        assert_eq!(
+            std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+
+        let tmp = create_copy(&dir, b"127.0.0.1 localhost\n").unwrap();
+        assert_eq!(
+            std::fs::metadata(tmp).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        // an existing file is never overwritten
+        assert!(create_copy(&dir, b"").is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(other).unwrap();
+    }
+}
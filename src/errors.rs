@@ -24,17 +24,44 @@ pub enum ApplicationError {
     #[error("An entry exists with the hostname, but with a different IP:\n{0}")]
     HostnameAlreadyInUse(String),
 
+    #[error("Not a valid hostname: {0}")]
+    InvalidHostname(String),
+
     #[error("No such hostname available in the hostfile: {0}")]
     HostnameDoesNotExist(String),
 
+    #[error("No entries for this address in the hostfile: {0}")]
+    AddressNotFound(String),
+
     #[error("Could not add host, no parent domain to resolve it. This means that no parent domain exists for the given hostname, try adding it with an IP address, it will be the first entry for this host.")]
     NoParentDomain(),
 
     #[error("You should not see this message, if you do, please log an bug report at https://github.com/credmp/hed, it is very appreciated!")]
     FileABugReport(),
 
-		#[error("File is not parseable: {0}")]
-		FileNotParseable(String),
+    #[error("File is not parseable: {0}")]
+    FileNotParseable(String),
+
+    #[error("Could not run the editor: {0}")]
+    EditorFailed(String),
+
+    #[error("Editing aborted, the hostfile was not changed")]
+    EditAborted(),
+
+    #[error("The hostfile has {0} problem(s)")]
+    ValidationFailed(usize),
+
+    #[error("Configuration is not valid: {0}")]
+    ConfigNotParseable(String),
+
+    #[error("The system resolver does not return the hostfile entry for {0}")]
+    ResolutionMismatch(String),
+
+    #[error("Unknown option: {0}")]
+    UnknownOption(String),
+
+    #[error("The address family does not match: {0}")]
+    FamilyMismatch(String),
 
     /// Represents all other cases of `std::io::Error`.
     #[error(transparent)]
@@ -0,0 +1,117 @@
+use std::{
+    env,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use faccess::PathExt;
+use hed::{ApplicationError, HostFile};
+use serde::Deserialize;
+
+/// How privileges are obtained when the hostfile is not writable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Escalation {
+    /// Run the write helper with `sudo`
+    Sudo,
+    /// Run the write helper with `doas`
+    Doas,
+    /// Run the write helper with polkit's `pkexec`
+    Pkexec,
+    /// Run the write helper with systemd's `run0`
+    Run0,
+    /// Never escalate, fail when the hostfile is not writable
+    None,
+}
+
+impl Escalation {
+    /// The program used to run the write helper
+    fn program(&self) -> Option<&'static str> {
+        match self {
+            Escalation::Sudo => Some("sudo"),
+            Escalation::Doas => Some("doas"),
+            Escalation::Pkexec => Some("pkexec"),
+            Escalation::Run0 => Some("run0"),
+            Escalation::None => None,
+        }
+    }
+}
+
+impl FromStr for Escalation {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sudo" => Ok(Escalation::Sudo),
+            "doas" => Ok(Escalation::Doas),
+            "pkexec" => Ok(Escalation::Pkexec),
+            "run0" => Ok(Escalation::Run0),
+            "none" => Ok(Escalation::None),
+            _ => Err(ApplicationError::ConfigNotParseable(format!(
+                "unknown escalation method `{}`, use sudo, doas, pkexec, run0 or none",
+                s
+            ))),
+        }
+    }
+}
+
+/// Write the hostfile. When it is not writable for the current user only the
+/// rendered file is handed to `hed write-helper`, which runs with the
+/// privileges obtained through `method`. The helper only writes the hostfile
+/// of the system wide configuration, with the backup settings from there.
+pub fn write(hf: &HostFile, method: Escalation) -> Result<(), ApplicationError> {
+    if hf.is_stdio() || Path::new(&hf.filename).writable() {
+        return hf.write();
+    }
+
+    let program = match method.program() {
+        Some(p) => p,
+        None => {
+            return Err(ApplicationError::HostFileUnwritable(format!(
+                "{} is not writable and privilege escalation is disabled. Run hed as a user that can write the file or allow escalation with the `escalation` setting",
+                hf.filename
+            )))
+        }
+    };
+    let failed = |e: String| ApplicationError::HostFileUnwritable(format!("{}: {}", program, e));
+
+    let mut content = vec![];
+    hf.write_to(&mut content)?;
+
+    let exe = env::current_exe().map_err(|e| failed(e.to_string()))?;
+    let mut helper = Command::new(program);
+    // the helper checks the file is the one it writes
+    helper
+        .arg(exe)
+        .arg("--file")
+        .arg(&hf.filename)
+        .arg("write-helper");
+
+    let mut child = helper
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&content)?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(failed(format!("write helper exited with {}", status)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::escalate::Escalation;
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("doas".parse::<Escalation>().unwrap(), Escalation::Doas);
+        assert_eq!("run0".parse::<Escalation>().unwrap(), Escalation::Run0);
+        assert_eq!("none".parse::<Escalation>().unwrap(), Escalation::None);
+        assert!("su".parse::<Escalation>().is_err());
+    }
+}
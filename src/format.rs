@@ -0,0 +1,327 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::errors::ApplicationError;
+use crate::hostentry::HostEntry;
+
+/// How the columns of the host entries are aligned when the hostfile is
+/// written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    /// Separate the columns with a single tab
+    #[default]
+    None,
+    /// Pad the columns with spaces to the widest value in the file
+    File,
+    /// Pad the columns with spaces to the widest value in the section, the
+    /// lines between blank lines or comments
+    Section,
+}
+
+impl FromStr for Align {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Align::None),
+            "file" => Ok(Align::File),
+            "section" => Ok(Align::Section),
+            _ => Err(ApplicationError::UnknownOption(format!(
+                "align `{}`, use none, file or section",
+                s
+            ))),
+        }
+    }
+}
+
+/// The conventions of the operating system the hostfile is written for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flavor {
+    #[default]
+    Unix,
+    /// `\r\n` line endings, a hostname per line and no more than
+    /// `WINDOWS_MAX_NAMES` names on a line. Windows resolves `localhost`
+    /// itself, the stock file has it commented out.
+    Windows,
+}
+
+/// The number of names Windows reads from a line of the hostfile
+pub const WINDOWS_MAX_NAMES: usize = 9;
+
+impl FromStr for Flavor {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unix" => Ok(Flavor::Unix),
+            "windows" => Ok(Flavor::Windows),
+            _ => Err(ApplicationError::UnknownOption(format!(
+                "flavor `{}`, use unix or windows",
+                s
+            ))),
+        }
+    }
+}
+
+/// The line ending of a hostfile
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// `\n`, as used on Unix
+    #[default]
+    Lf,
+    /// `\r\n`, as used on Windows
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            _ => Err(ApplicationError::UnknownOption(format!(
+                "line ending `{}`, use lf or crlf",
+                s
+            ))),
+        }
+    }
+}
+
+/// The widths of the IP address, name and alias columns
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Widths {
+    ip: usize,
+    name: usize,
+    aliasses: usize,
+}
+
+impl Widths {
+    fn of(entries: &[HostEntry]) -> Widths {
+        let mut w = Widths::default();
+        for e in entries
+            .iter()
+            .filter(|e| e.ip.is_some() && e.name.is_some())
+        {
+            w.ip = w.ip.max(e.address().unwrap().len());
+            w.name = w.name.max(e.name.as_ref().unwrap().len());
+            w.aliasses = w.aliasses.max(aliasses(e).len());
+        }
+        w
+    }
+}
+
+fn aliasses(entry: &HostEntry) -> String {
+    entry
+        .aliasses
+        .as_ref()
+        .map(|a| a.join(" "))
+        .unwrap_or_default()
+}
+
+/// The maximum number of names and characters on a line of the hostfile,
+/// no limit when `None`. The length is measured without the alignment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub names: Option<usize>,
+    pub line_length: Option<usize>,
+}
+
+impl Limits {
+    /// Indicate if `entry` stays within the limits when `name` is added to it
+    pub fn fits(&self, entry: &HostEntry, name: &str) -> bool {
+        if matches!(self.names, Some(max) if entry.names().len() >= max) {
+            return false;
+        }
+        match self.line_length {
+            Some(max) => {
+                let mut longer = entry.clone();
+                longer.add_alias(name);
+                longer.to_string().chars().count() <= max
+            }
+            None => true,
+        }
+    }
+}
+
+/// Join the lines that `HostEntry::split` made of an entry back into one
+/// entry: a line for the same IP address, without a comment, that follows a
+/// line that was full. Every entry comes with the number of lines it was
+/// joined from.
+pub fn join(entries: &[HostEntry], limits: &Limits) -> Vec<(HostEntry, usize)> {
+    let mut joined: Vec<(HostEntry, usize)> = vec![];
+    let mut previous: Option<&HostEntry> = None;
+    for entry in entries {
+        let continues = match (previous, &entry.name) {
+            (Some(p), Some(name)) => {
+                entry.ip.is_some()
+                    && entry.comment.is_none()
+                    && p.ip == entry.ip
+                    && !limits.fits(p, name)
+            }
+            _ => false,
+        };
+        match joined.last_mut() {
+            Some((last, count)) if continues => {
+                for name in entry.names() {
+                    last.add_alias(name);
+                }
+                *count += 1;
+            }
+            _ => joined.push((entry.clone(), 1)),
+        }
+        previous = Some(entry);
+    }
+    joined
+}
+
+/// Render the entries as lines of the hostfile, aligned as set by `align`.
+/// Entries that do not stay within `limits` are split over several lines.
+pub fn render(entries: &[HostEntry], align: Align, limits: &Limits) -> Vec<String> {
+    let entries: Vec<HostEntry> = entries.iter().flat_map(|e| e.split(limits)).collect();
+    let entries = entries.as_slice();
+    let file = Widths::of(entries);
+
+    let mut lines = vec![];
+
+    let mut start = 0;
+    for (idx, entry) in entries.iter().enumerate() {
+        if align == Align::None || entry.ip.is_none() {
+            // blank lines and comments start a new section
+            start = idx + 1;
+            lines.push(entry.to_string());
+            continue;
+        }
+        let widths = match align {
+            Align::None | Align::File => file,
+            Align::Section => {
+                let end = entries[idx..]
+                    .iter()
+                    .position(|e| e.ip.is_none())
+                    .map_or(entries.len(), |p| idx + p);
+                Widths::of(&entries[start..end])
+            }
+        };
+        lines.push(aligned(entry, widths));
+    }
+    lines
+}
+
+/// The entry with its columns padded to `w`, without trailing whitespace
+fn aligned(entry: &HostEntry, w: Widths) -> String {
+    let (ip, name) = match (entry.address(), &entry.name) {
+        (Some(ip), Some(name)) => (ip, name),
+        _ => return entry.to_string(),
+    };
+    let mut columns = vec![format!("{:<1$}", ip, w.ip), format!("{:<1$}", name, w.name)];
+    let a = aliasses(entry);
+    if let Some(c) = &entry.comment {
+        columns.push(format!("{:<1$}", a, w.aliasses));
+        columns.push(format!("# {}", c));
+    } else if !a.is_empty() {
+        columns.push(a);
+    }
+    columns.join(" ").trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::{join, render, Align, Limits, LineEnding};
+    use crate::HostEntry;
+
+    #[test]
+    fn test_render() {
+        let entries: Vec<HostEntry> = [
+            "127.0.0.1 localhost",
+            "10.10.10.196 box.htb admin.box.htb # lab",
+            "",
+            "::1 localhost ip6-localhost",
+            "ff02::1 ip6-allnodes",
+        ]
+        .iter()
+        .map(|l| l.parse().unwrap())
+        .collect();
+
+        assert_eq!(
+            render(&entries, Align::None, &Limits::default())[0],
+            "127.0.0.1\tlocalhost"
+        );
+        assert_eq!(
+            render(&entries, Align::File, &Limits::default()),
+            vec![
+                "127.0.0.1    localhost",
+                "10.10.10.196 box.htb      admin.box.htb # lab",
+                "",
+                "::1          localhost    ip6-localhost",
+                "ff02::1      ip6-allnodes",
+            ]
+        );
+        assert_eq!(
+            render(&entries, Align::Section, &Limits::default()),
+            vec![
+                "127.0.0.1    localhost",
+                "10.10.10.196 box.htb   admin.box.htb # lab",
+                "",
+                "::1     localhost    ip6-localhost",
+                "ff02::1 ip6-allnodes",
+            ]
+        );
+        assert_eq!("section".parse::<Align>().unwrap(), Align::Section);
+        assert!("right".parse::<Align>().is_err());
+    }
+
+    #[test]
+    fn test_join() {
+        let limits = Limits {
+            names: Some(3),
+            line_length: None,
+        };
+        let entries: Vec<HostEntry> = [
+            "10.0.0.1 a b c # lab",
+            "10.0.0.1 d e f",
+            "10.0.0.1 g",
+            "10.0.0.1 h",
+            "10.0.0.2 i j k",
+            "10.0.0.1 l",
+        ]
+        .iter()
+        .map(|l| l.parse().unwrap())
+        .collect();
+        let (joined, counts): (Vec<HostEntry>, Vec<usize>) =
+            join(&entries, &limits).into_iter().unzip();
+        assert_eq!(counts, vec![3, 1, 1, 1]);
+        assert_eq!(joined[0].names(), vec!["a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(
+            render(&joined, Align::None, &limits),
+            vec![
+                "10.0.0.1\ta\tb c\t# lab",
+                "10.0.0.1\td\te f",
+                "10.0.0.1\tg",
+                "10.0.0.1\th",
+                "10.0.0.2\ti\tj k",
+                "10.0.0.1\tl",
+            ]
+        );
+        assert_eq!(join(&entries, &Limits::default()).len(), 6);
+    }
+
+    #[test]
+    fn test_line_ending() {
+        assert_eq!("crlf".parse::<LineEnding>().unwrap(), LineEnding::Crlf);
+        assert_eq!(LineEnding::Crlf.as_str(), "\r\n");
+        assert!("cr".parse::<LineEnding>().is_err());
+    }
+}
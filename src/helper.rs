@@ -0,0 +1,141 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    process,
+};
+
+use hed::{ApplicationError, HostEntry};
+
+use crate::config::Config;
+
+/// Replace the hostfile with the content read from stdin, after making a
+/// backup. This is the only part of hed that runs with elevated privileges.
+/// The hostfile and the backup settings come from the system wide
+/// configuration or the defaults, never from the caller: `requested`, the
+/// file the caller wants to write, has to be that hostfile.
+pub fn write_helper(requested: Option<&str>) -> Result<(), ApplicationError> {
+    let hf = Config::system()?.hostfile(None);
+    if let Some(requested) = requested {
+        if !same_file(requested, &hf.filename) {
+            return Err(ApplicationError::HostFileUnwritable(format!(
+                "the write helper only writes {}, not {}; set `file` in /etc/hed.toml to manage another hosts file",
+                hf.filename, requested
+            )));
+        }
+    }
+
+    let mut content = vec![];
+    io::stdin().read_to_end(&mut content)?;
+    check_content(&String::from_utf8_lossy(&content))?;
+
+    hf.backup()?;
+    replace(&hf.filename, &content)
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => Path::new(a) == Path::new(b),
+    }
+}
+
+/// Write the content to a new file next to the hostfile and rename it over
+/// the hostfile, so an interrupted write never leaves it truncated. The new
+/// file gets the permissions of the hostfile, a symlinked hostfile is
+/// replaced where the link points to.
+fn replace(filename: &str, content: &[u8]) -> Result<(), ApplicationError> {
+    let failed =
+        |e: io::Error| ApplicationError::HostFileUnwritable(format!("{}: {}", filename, e));
+
+    let target = fs::canonicalize(filename).map_err(failed)?;
+    let permissions = fs::metadata(&target).map_err(failed)?.permissions();
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp = target.with_file_name(format!(".{}.hed-{}", name, process::id()));
+
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp)
+        .and_then(|mut file| {
+            file.write_all(content)?;
+            file.set_permissions(permissions)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, &target));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written.map_err(failed)
+}
+
+/// Make sure the content is a hostfile: every line is empty, a comment or
+/// starts with an IP address followed by a hostname.
+fn check_content(content: &str) -> Result<(), ApplicationError> {
+    for (idx, line) in content.lines().enumerate() {
+        let l = line.trim();
+        if l.is_empty() || l.starts_with('#') {
+            continue;
+        }
+        let valid = match l.parse::<HostEntry>() {
+            Ok(entry) => entry.ip.is_some() && entry.name.is_some(),
+            Err(_) => false,
+        };
+        if !valid {
+            return Err(ApplicationError::FileNotParseable(format!(
+                "refusing to write, line {} is not a host entry: {}",
+                idx + 1,
+                line
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helper::{check_content, replace};
+
+    #[test]
+    fn test_check_content() {
+        assert!(check_content("# hosts\n127.0.0.1\tlocalhost\n\n::1 localhost # v6\n").is_ok());
+        assert!(check_content("").is_ok());
+        assert!(check_content("127.0.0.1\tlocalhost\nrm -rf /\n").is_err());
+        assert!(check_content("localhost 127.0.0.1\n").is_err());
+    }
+
+    #[test]
+    fn test_replace() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("hed-helper-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let hosts = dir.join("hosts");
+        std::fs::write(&hosts, "127.0.0.1 localhost\n").unwrap();
+        std::fs::set_permissions(&hosts, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let link = dir.join("link");
+        std::os::unix::fs::symlink(&hosts, &link).unwrap();
+
+        replace(&link.display().to_string(), b"10.0.0.1 box.htb\n").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&hosts).unwrap(),
+            "10.0.0.1 box.htb\n"
+        );
+        assert_eq!(
+            std::fs::metadata(&hosts).unwrap().permissions().mode() & 0o777,
+            0o644
+        );
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,13 +1,55 @@
-use std::{fmt, io::Write, net::IpAddr, str::FromStr, string::ParseError};
+use std::{
+    fmt,
+    io::{self, Write},
+    net::IpAddr,
+    str::FromStr,
+    string::ParseError,
+};
 
 use regex::Regex;
-use termion::color;
 
+use crate::errors::ApplicationError;
+use crate::format::Limits;
+use crate::theme::Theme;
 use crate::utils::Modifications;
 
+/// The address family of an IP address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    pub fn of(ip: &IpAddr) -> Family {
+        match ip {
+            IpAddr::V4(_) => Family::V4,
+            IpAddr::V6(_) => Family::V6,
+        }
+    }
+}
+
+impl FromStr for Family {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "4" | "ipv4" => Ok(Family::V4),
+            "6" | "ipv6" => Ok(Family::V6),
+            _ => Err(ApplicationError::UnknownOption(format!(
+                "family `{}`, use 4 or 6",
+                s
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HostEntry {
     pub ip: Option<IpAddr>,
+    /// Zone of a link-local IPv6 address, the interface after the `%` in
+    /// `fe80::1%eth0`
+    pub zone: Option<String>,
     pub name: Option<String>,
     pub aliasses: Option<Vec<String>>,
     pub comment: Option<String>,
@@ -17,47 +59,52 @@ impl HostEntry {
     pub fn empty() -> HostEntry {
         HostEntry {
             ip: None,
+            zone: None,
             name: None,
             aliasses: None,
             comment: None,
         }
     }
 
-    pub fn color_print<W: Write>(&self, f: &mut W) -> Result<(), Box<dyn std::error::Error>> {
+    /// Print the entry to `f` using the colors of `theme`
+    pub fn color_print<W: Write>(&self, f: &mut W, theme: &Theme) -> io::Result<()> {
         if self.ip.is_none() && self.comment.is_some() {
             writeln!(
                 f,
                 "{}# {}{}",
-                color::Fg(color::LightBlue),
+                theme.comment,
                 self.comment.as_ref().unwrap(),
-                color::Fg(color::Reset),
+                theme.reset,
             )?;
         } else if self.ip.is_some() {
             write!(
                 f,
                 "{}{}\t{}{}\t{}{}",
-                color::Fg(color::Cyan),
-                self.ip.unwrap(),
-                color::Fg(color::LightMagenta),
+                theme.ip,
+                self.address().unwrap(),
+                theme.name,
                 self.name.as_ref().unwrap(),
-                color::Fg(color::LightGreen),
+                theme.alias,
                 self.aliasses.as_ref().unwrap_or(&vec![]).join(" ")
             )?;
             if self.comment.is_some() {
-                write!(
-                    f,
-                    "{}\t# {}",
-                    color::Fg(color::LightBlue),
-                    self.comment.as_ref().unwrap()
-                )?;
+                write!(f, "{}\t# {}", theme.comment, self.comment.as_ref().unwrap())?;
             }
-            writeln!(f, "{}", color::Fg(color::Reset))?;
+            writeln!(f, "{}", theme.reset)?;
         } else {
             writeln!(f)?;
         }
         Ok(())
     }
 
+    /// The IP address with its zone, as written in the hostfile
+    pub fn address(&self) -> Option<String> {
+        self.ip.map(|ip| match &self.zone {
+            Some(zone) => format!("{}%{}", ip, zone),
+            None => ip.to_string(),
+        })
+    }
+
     /// Checks if the `name` of `HostEntry` can resolve the passed `hostname`.
     ///
     /// If `name` is `host.tld` and `hostname` is a subdomain, return true.
@@ -99,6 +146,17 @@ impl HostEntry {
         }
     }
 
+    /// Whether the entry is for the IP address in the zone, `fe80::1%eth0`
+    /// and `fe80::1%wlan0` are different addresses
+    pub fn has_address(&self, ip: &IpAddr, zone: Option<&str>) -> bool {
+        self.has_ip(ip) && self.zone.as_deref() == zone
+    }
+
+    /// Indicate if the IP address of the entry is of `family`
+    pub fn is_family(&self, family: Family) -> bool {
+        self.ip.as_ref().map(Family::of) == Some(family)
+    }
+
     pub fn has_name(&self, hostname: &str) -> bool {
         if let Some(x) = &self.name {
             if x.eq(hostname) {
@@ -120,7 +178,25 @@ impl HostEntry {
         false
     }
 
-    pub(crate) fn add_alias(&mut self, hostname: &str) {
+    /// All names of the entry, the `name` followed by the `aliasses`
+    pub fn names(&self) -> Vec<&str> {
+        self.name
+            .iter()
+            .chain(self.aliasses.iter().flatten())
+            .map(|n| n.as_str())
+            .collect()
+    }
+
+    /// All names of this entry that are not valid hostnames according to
+    /// RFC 1123.
+    pub fn invalid_names(&self) -> Vec<&str> {
+        self.names()
+            .into_iter()
+            .filter(|n| !is_valid_hostname(n))
+            .collect()
+    }
+
+    pub fn add_alias(&mut self, hostname: &str) {
         if self.aliasses.is_some() {
             let mut alias: Vec<String> = self.aliasses.as_ref().unwrap().clone();
             alias.push(hostname.to_string());
@@ -130,6 +206,82 @@ impl HostEntry {
         }
     }
 
+    /// The entry as lines for the same IP address that stay within `limits`,
+    /// the comment stays on the first line
+    pub fn split(&self, limits: &Limits) -> Vec<HostEntry> {
+        if self.ip.is_none() || self.name.is_none() {
+            return vec![self.clone()];
+        }
+        let mut lines = vec![];
+        let mut line = HostEntry {
+            ip: self.ip,
+            zone: self.zone.clone(),
+            name: self.name.clone(),
+            aliasses: None,
+            comment: self.comment.clone(),
+        };
+        for alias in self.aliasses.iter().flatten() {
+            if limits.fits(&line, alias) {
+                line.add_alias(alias);
+            } else {
+                lines.push(line);
+                line = HostEntry {
+                    ip: self.ip,
+                    zone: self.zone.clone(),
+                    name: Some(alias.clone()),
+                    aliasses: None,
+                    comment: None,
+                };
+            }
+        }
+        lines.push(line);
+        lines
+    }
+
+    /// Rename `old` to `new`, in the same position. Returns if the entry had
+    /// the name `old`.
+    pub fn rename(&mut self, old: &str, new: &str) -> bool {
+        for name in self
+            .name
+            .iter_mut()
+            .chain(self.aliasses.iter_mut().flatten())
+        {
+            if name == old {
+                *name = String::from(new);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Make the alias `hostname` the canonical `name`, the current name
+    /// becomes the first alias. Returns if the entry changed.
+    pub fn make_canonical(&mut self, hostname: &str) -> bool {
+        let aliasses = match &mut self.aliasses {
+            Some(a) if a.iter().any(|n| n == hostname) => a,
+            _ => return false,
+        };
+        aliasses.retain(|n| n != hostname);
+        if let Some(old) = self.name.replace(String::from(hostname)) {
+            aliasses.insert(0, old);
+        }
+        true
+    }
+
+    /// The first alias that is a fully qualified domain name when the
+    /// canonical `name` is not one
+    pub fn fqdn_alias(&self) -> Option<&str> {
+        match &self.name {
+            Some(n) if !n.trim_end_matches('.').contains('.') => self
+                .aliasses
+                .iter()
+                .flatten()
+                .map(|a| a.as_str())
+                .find(|a| a.trim_end_matches('.').contains('.')),
+            _ => None,
+        }
+    }
+
     /// a hostname is given that can be the parent domain
     /// make the hostname the `name` and move the current
     /// `name` to aliases    
@@ -152,7 +304,7 @@ impl HostEntry {
     ///
     /// - Removing an alias
     /// - Remove the name, chosing the shortest alias as the new name
-    pub(crate) fn remove_hostname(&mut self, name: &str) -> (Modifications, HostEntry) {
+    pub fn remove_hostname(&mut self, name: &str) -> (Modifications, HostEntry) {
         let mut mods = Modifications::new();
 
         // if it is the name that needs to be removed
@@ -179,8 +331,13 @@ impl HostEntry {
                         mods,
                         HostEntry {
                             ip: self.ip,
+                            zone: self.zone.clone(),
                             name: Some(shortest),
-                            aliasses: Some(others),
+                            aliasses: if others.is_empty() {
+                                None
+                            } else {
+                                Some(others)
+                            },
                             comment: self.comment.clone(),
                         },
                     );
@@ -202,8 +359,13 @@ impl HostEntry {
                     mods,
                     HostEntry {
                         ip: self.ip,
+                        zone: self.zone.clone(),
                         name: self.name.clone(),
-                        aliasses: Some(others),
+                        aliasses: if others.is_empty() {
+                            None
+                        } else {
+                            Some(others)
+                        },
                         comment: self.comment.clone(),
                     },
                 );
@@ -213,9 +375,45 @@ impl HostEntry {
     }
 }
 
+/// Parse an IP address, an IPv6 address can have a zone: `fe80::1%eth0`
+pub fn parse_address(address: &str) -> Option<(IpAddr, Option<String>)> {
+    match address.split_once('%') {
+        Some((ip, zone)) => match ip.parse() {
+            Ok(ip @ IpAddr::V6(_)) if !zone.is_empty() => Some((ip, Some(String::from(zone)))),
+            _ => None,
+        },
+        None => address.parse().ok().map(|ip| (ip, None)),
+    }
+}
+
+/// Checks if `hostname` is a valid RFC 1123 hostname: dot separated labels of
+/// at most 63 letters, digits or hyphens, not starting or ending with a hyphen.
+pub fn is_valid_hostname(hostname: &str) -> bool {
+    let hostname = hostname.strip_suffix('.').unwrap_or(hostname);
+
+    !hostname.is_empty() && hostname.len() <= 253 && hostname.split('.').all(is_valid_label)
+}
+
+/// Checks a single label, by hand as `validate` does this for every name
+fn is_valid_label(label: &str) -> bool {
+    let bytes = label.as_bytes();
+    match (bytes.first(), bytes.last()) {
+        (Some(first), Some(last)) => {
+            bytes.len() <= 63
+                && *first != b'-'
+                && *last != b'-'
+                && bytes
+                    .iter()
+                    .all(|b| b.is_ascii_alphanumeric() || *b == b'-')
+        }
+        _ => false,
+    }
+}
+
 impl PartialEq for HostEntry {
     fn eq(&self, other: &Self) -> bool {
         self.ip == other.ip
+            && self.zone == other.zone
             && self.name == other.name
             && self.aliasses == other.aliasses
             && self.comment == other.comment
@@ -232,7 +430,7 @@ impl FromStr for HostEntry {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let comment = Regex::new(r"^#\s*(?P<c>.+)\s*$").unwrap();
+        let comment = Regex::new(r"^\s*#\s*(?P<c>.*)$").unwrap();
         let entry =
             Regex::new(r"^(?P<ip>.+?)\s+(?P<name>.+?)(\s+(?P<aliasses>[^#]+))?(#\s*(?P<c>.*))?$")
                 .unwrap();
@@ -241,6 +439,7 @@ impl FromStr for HostEntry {
                 .captures(s)
                 .map(|cap| HostEntry {
                     ip: None,
+                    zone: None,
                     name: None,
                     aliasses: None,
                     comment: cap.name("c").map(|t| String::from(t.as_str().trim())),
@@ -250,9 +449,9 @@ impl FromStr for HostEntry {
             let caps = entry.captures(s).unwrap();
             let ip_str = caps.name("ip").map(|t| t.as_str()).unwrap();
 
-            let ip: Option<IpAddr> = match ip_str.parse() {
-                Ok(x) => Some(x),
-                _ => None,
+            let (ip, zone) = match parse_address(ip_str) {
+                Some((ip, zone)) => (Some(ip), zone),
+                None => (None, None),
             };
 
             let name = caps.name("name").map(|t| String::from(t.as_str().trim()));
@@ -267,6 +466,7 @@ impl FromStr for HostEntry {
             let comment = caps.name("c").map(|t| String::from(t.as_str().trim()));
             Ok(HostEntry {
                 ip,
+                zone,
                 name,
                 aliasses: alias_vec,
                 comment,
@@ -274,6 +474,7 @@ impl FromStr for HostEntry {
         } else {
             Ok(HostEntry {
                 ip: None,
+                zone: None,
                 name: None,
                 aliasses: None,
                 comment: None,
@@ -284,10 +485,17 @@ impl FromStr for HostEntry {
 
 impl fmt::Display for HostEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.ip.is_none() && self.comment.is_some() {
+        if self.ip.is_none() && self.comment.as_deref() == Some("") {
+            write!(f, "#")
+        } else if self.ip.is_none() && self.comment.is_some() {
             write!(f, "# {}", self.comment.as_ref().unwrap(),)
         } else if self.ip.is_some() {
-            write!(f, "{}\t{}", self.ip.unwrap(), self.name.as_ref().unwrap(),)?;
+            write!(
+                f,
+                "{}\t{}",
+                self.address().unwrap(),
+                self.name.as_ref().unwrap(),
+            )?;
             if self.aliasses.is_some() {
                 write!(
                     f,
@@ -312,7 +520,8 @@ mod test {
 
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
-    use crate::hostentry::HostEntry;
+    use crate::format::Limits;
+    use crate::hostentry::{is_valid_hostname, parse_address, Family, HostEntry};
 
     #[test]
     fn test_ip_addr() {
@@ -328,6 +537,7 @@ mod test {
         let ip = "127.0.0.1".parse().expect("Should parse localhost");
         let mut he = HostEntry {
             ip: Some(ip),
+            zone: None,
             name: Some(String::from("arjenwiersma.nl")),
             aliasses: None,
             comment: None,
@@ -347,6 +557,7 @@ mod test {
             he.remove_hostname("arjenwiersma.nl").1,
             HostEntry {
                 ip: Some(ip),
+                zone: None,
                 name: Some(String::from("d.arjenwiersma.nl")),
                 aliasses: Some(vec![String::from("demo.arjenwiersma.nl")]),
                 comment: None,
@@ -357,6 +568,7 @@ mod test {
             he.remove_hostname("demo.arjenwiersma.nl").1,
             HostEntry {
                 ip: Some(ip),
+                zone: None,
                 name: Some(String::from("arjenwiersma.nl")),
                 aliasses: Some(vec![String::from("d.arjenwiersma.nl")]),
                 comment: None,
@@ -369,6 +581,7 @@ mod test {
         let ip = "127.0.0.1".parse().expect("Should parse localhost");
         let mut he = HostEntry {
             ip: Some(ip),
+            zone: None,
             name: Some(String::from("arjenwiersma.nl")),
             aliasses: None,
             comment: None,
@@ -382,6 +595,7 @@ mod test {
         let ip = "127.0.0.1".parse().expect("Should parse localhost");
         let mut he = HostEntry {
             ip: Some(ip),
+            zone: None,
             name: Some(String::from("arjenwiersma.nl")),
             aliasses: None,
             comment: None,
@@ -398,11 +612,38 @@ mod test {
         assert_eq!("127.0.0.1\tarjenwiersma.nl\thost1 host2", format!("{}", he));
     }
 
+    #[test]
+    fn test_valid_hostname() {
+        assert!(is_valid_hostname("localhost"));
+        assert!(is_valid_hostname("arjenwiersma.nl"));
+        assert!(is_valid_hostname("ip6-allnodes"));
+        assert!(is_valid_hostname("10.htb."));
+        assert!(!is_valid_hostname(""));
+        assert!(!is_valid_hostname("-demo.nl"));
+        assert!(!is_valid_hostname("demo..nl"));
+        assert!(!is_valid_hostname("demo_host.nl"));
+        assert!(!is_valid_hostname("10.10.10.10/24"));
+        assert!(!is_valid_hostname("demo-.nl"));
+        assert!(!is_valid_hostname("café.nl"));
+        assert!(is_valid_hostname(&format!("{}.nl", "a".repeat(63))));
+        assert!(!is_valid_hostname(&format!("{}.nl", "a".repeat(64))));
+
+        let he = HostEntry {
+            ip: None,
+            zone: None,
+            name: Some(String::from("arjenwiersma.nl")),
+            aliasses: Some(vec![String::from("ok.nl"), String::from("not ok!")]),
+            comment: None,
+        };
+        assert_eq!(he.invalid_names(), vec!["not ok!"]);
+    }
+
     #[test]
     fn test_has_ip() {
         let ip = "127.0.0.1".parse().expect("Should parse localhost");
         let he = HostEntry {
             ip: Some(ip),
+            zone: None,
             name: None,
             aliasses: None,
             comment: None,
@@ -418,6 +659,7 @@ mod test {
     fn test_hostentry_has_name() {
         let he = HostEntry {
             ip: None,
+            zone: None,
             name: Some(String::from("arjen.wiersma.nl")),
             aliasses: None,
             comment: None,
@@ -429,6 +671,7 @@ mod test {
 
         let ahe = HostEntry {
             ip: None,
+            zone: None,
             name: Some(String::from("jelle.wiersma.nl")),
             aliasses: Some(vec![
                 String::from("arjen.wiersma.nl"),
@@ -447,6 +690,7 @@ mod test {
     fn test_can_resolve() {
         let he = HostEntry {
             ip: None,
+            zone: None,
             name: Some(String::from("wiersma.nl")),
             aliasses: None,
             comment: None,
@@ -461,6 +705,7 @@ mod test {
     fn test_hostname_can_resolve() {
         let he = HostEntry {
             ip: None,
+            zone: None,
             name: Some(String::from("arjen.wiersma.nl")),
             aliasses: None,
             comment: None,
@@ -475,6 +720,7 @@ mod test {
     fn test_switch_name() {
         let mut he = HostEntry {
             ip: None,
+            zone: None,
             name: Some(String::from("arjen.wiersma.nl")),
             aliasses: None,
             comment: None,
@@ -529,4 +775,89 @@ mod test {
 
         assert_eq!("from string", entry.comment.unwrap());
     }
+
+    #[test]
+    fn test_from_string_bare_comment() {
+        let entry: HostEntry = "#".parse().unwrap();
+        assert_eq!(entry.comment.as_deref(), Some(""));
+        assert_eq!(entry.to_string(), "#");
+
+        let entry: HostEntry = "   #\tnote".parse().unwrap();
+        assert_eq!(entry.comment.as_deref(), Some("note"));
+        assert!(entry.ip.is_none());
+    }
+
+    #[test]
+    fn test_split() {
+        let entry: HostEntry = "10.0.0.1 a b c d e # lab".parse().unwrap();
+        let names = Limits {
+            names: Some(2),
+            line_length: None,
+        };
+        let lines: Vec<String> = entry.split(&names).iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec!["10.0.0.1\ta\tb\t# lab", "10.0.0.1\tc\td", "10.0.0.1\te"]
+        );
+        assert_eq!(entry.split(&Limits::default()).len(), 1);
+
+        let length = Limits {
+            names: None,
+            line_length: Some(14),
+        };
+        let lines: Vec<String> = "10.0.0.1 a b c d e"
+            .parse::<HostEntry>()
+            .unwrap()
+            .split(&length)
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(lines, vec!["10.0.0.1\ta\tb c", "10.0.0.1\td\te"]);
+    }
+
+    #[test]
+    fn test_zone() {
+        let entry: HostEntry = "fe80::1%eth0 router.lan".parse().unwrap();
+        assert_eq!(entry.ip, Some("fe80::1".parse().unwrap()));
+        assert_eq!(entry.zone.as_deref(), Some("eth0"));
+        assert_eq!(entry.to_string(), "fe80::1%eth0\trouter.lan");
+
+        assert_eq!(parse_address("10.0.0.1%eth0"), None);
+        assert_eq!(parse_address("fe80::1%"), None);
+        assert_eq!(parse_address("::1"), Some(("::1".parse().unwrap(), None)));
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut entry: HostEntry = "10.0.0.1 a.htb b.htb c.htb # lab".parse().unwrap();
+        assert!(entry.rename("b.htb", "d.htb"));
+        assert!(entry.rename("a.htb", "e.htb"));
+        assert!(!entry.rename("x.htb", "y.htb"));
+        assert_eq!(entry.to_string(), "10.0.0.1\te.htb\td.htb c.htb\t# lab");
+
+        let (_, mut rest) = entry.remove_hostname("d.htb");
+        let (_, rest) = rest.remove_hostname("c.htb");
+        assert_eq!(rest.to_string(), "10.0.0.1\te.htb\t# lab");
+    }
+
+    #[test]
+    fn test_make_canonical() {
+        let mut entry: HostEntry = "10.0.0.1 box box.htb www.box.htb".parse().unwrap();
+        assert_eq!(entry.fqdn_alias(), Some("box.htb"));
+        assert!(entry.make_canonical("box.htb"));
+        assert!(!entry.make_canonical("box.htb"));
+        assert!(!entry.make_canonical("missing.htb"));
+        assert_eq!(entry.to_string(), "10.0.0.1\tbox.htb\tbox www.box.htb");
+        assert_eq!(entry.fqdn_alias(), None);
+    }
+
+    #[test]
+    fn test_family() {
+        let entry: HostEntry = "fd00::5 box.htb".parse().unwrap();
+        assert!(entry.is_family(Family::V6));
+        assert!(!entry.is_family(Family::V4));
+        assert!(!HostEntry::empty().is_family(Family::V4));
+        assert_eq!("4".parse::<Family>().unwrap(), Family::V4);
+        assert!("5".parse::<Family>().is_err());
+    }
 }
@@ -1,92 +1,744 @@
-use faccess::PathExt;
-use std::io::Write;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::{
     fs::{self, File},
-    net::IpAddr,
-    path::Path,
+    net::{IpAddr, Ipv6Addr},
+    ops::Range,
+    path::{Path, PathBuf},
 };
 
 use crate::errors::ApplicationError;
-use crate::hostentry::HostEntry;
-use crate::utils::{read_lines, Modifications};
+use crate::format::{self, Align, Flavor, Limits, LineEnding, WINDOWS_MAX_NAMES};
+use crate::hostentry::{is_valid_hostname, parse_address, Family, HostEntry};
+use crate::theme::Theme;
+use crate::utils::{Diagnostic, Issue, Modifications, Reverse, Shadowed};
+use crate::view::{Network, ShowOptions};
+
+/// Filename that reads the hostfile from stdin and writes it to stdout
+pub const STDIO: &str = "-";
 
 #[derive(Debug)]
 pub struct HostFile {
     pub filename: String,
     pub entries: Option<Vec<HostEntry>>,
+    /// Directory to store backups in, next to the hostfile when `None`
+    pub backup_dir: Option<PathBuf>,
+    /// Number of backups to keep, `0` disables backups
+    pub backup_retention: usize,
+    /// Header comment of the section new entries are added to
+    pub section: Option<String>,
+    /// How the columns are aligned when the hostfile is written
+    pub align: Align,
+    /// The operating system conventions the hostfile follows
+    pub flavor: Flavor,
+    /// The maximum number of names on a line, longer entries are split over
+    /// several lines for the same IP address
+    pub max_names: Option<usize>,
+    /// The maximum number of characters on a line, longer entries are split
+    /// over several lines for the same IP address
+    pub max_line_length: Option<usize>,
+    /// The line ending to write, the one of the file as read (or `\r\n` for
+    /// `Flavor::Windows`) when `None`
+    pub line_ending: Option<LineEnding>,
+    /// End the last line with a line ending, as the file read when `None`
+    pub final_newline: Option<bool>,
+    /// The lines as they were read by `parse`, bytes that are not valid UTF-8
+    /// replaced by `U+FFFD`
+    source: Vec<String>,
+    /// The entries as read by `parse`, with the indices in `source` of the
+    /// lines they were read from. An entry that `write_to` split over several
+    /// lines is read as one entry.
+    read: Vec<(HostEntry, Range<usize>)>,
+    /// The entries as read by `parse`, with the bytes of their lines. They
+    /// are written back as they were while the entry does not change.
+    raw: Vec<(HostEntry, Vec<Vec<u8>>)>,
+    /// The line ending of the file as read by `parse`
+    read_ending: LineEnding,
+    /// Indicate if the file read by `parse` ended with a line ending
+    read_final_newline: bool,
 }
 
 impl HostFile {
-    pub fn add_host_entry(&mut self, entry: HostEntry) {
-        if self.entries.is_some() {
-            let mut e: Vec<HostEntry> = self.entries.as_ref().unwrap().clone();
-            e.push(entry);
-            self.entries = Some(e);
-        } else {
-            self.entries = Some(vec![entry]);
+    /// Create a hostfile for `filename` without any entries, use `parse` to
+    /// read the entries from the file.
+    pub fn new<S: Into<String>>(filename: S) -> HostFile {
+        HostFile {
+            filename: filename.into(),
+            entries: None,
+            backup_dir: None,
+            backup_retention: 1,
+            section: None,
+            align: Align::None,
+            flavor: Flavor::Unix,
+            max_names: None,
+            max_line_length: None,
+            line_ending: None,
+            final_newline: None,
+            source: vec![],
+            read: vec![],
+            raw: vec![],
+            read_ending: LineEnding::Lf,
+            read_final_newline: true,
         }
     }
 
-    pub fn write(&self) -> Result<(), ApplicationError> {
-        //println!("Writing to file {}", self.filename);
+    /// Create a hostfile for `filename` and parse its entries
+    pub fn open<S: Into<String>>(filename: S) -> Result<HostFile, ApplicationError> {
+        let mut hf = HostFile::new(filename);
+        hf.parse()?;
+        Ok(hf)
+    }
 
-        let path = Path::new(&self.filename);
-        if !path.writable() {
-            if let Err(e) = sudo::escalate_if_needed() {
-                return Err(ApplicationError::HostFileUnwritable(e.to_string()));
+    /// Indicate if the hostfile is read from stdin and written to stdout
+    pub fn is_stdio(&self) -> bool {
+        self.filename == STDIO
+    }
+
+    /// Add the entry at the end of the file, or at the end of the `section`
+    /// when one is set. A missing section is created at the end of the file.
+    pub fn add_host_entry(&mut self, entry: HostEntry) {
+        let entries = self.entries.get_or_insert_with(Vec::new);
+        let section = match &self.section {
+            Some(s) => s,
+            None => {
+                entries.push(entry);
+                return;
+            }
+        };
+
+        let header = entries
+            .iter()
+            .position(|e| e.ip.is_none() && e.comment.as_deref() == Some(section.as_str()));
+        match header {
+            Some(h) => {
+                // the section ends at the first empty line or comment
+                let end = entries[h + 1..]
+                    .iter()
+                    .position(|e| e.ip.is_none())
+                    .map_or(entries.len(), |p| h + 1 + p);
+                entries.insert(end, entry);
+            }
+            None => {
+                if matches!(entries.last(), Some(e) if *e != HostEntry::empty()) {
+                    entries.push(HostEntry::empty());
+                }
+                entries.push(HostEntry {
+                    comment: Some(section.clone()),
+                    ..HostEntry::empty()
+                });
+                entries.push(entry);
             }
         }
+    }
 
-        self.backup()?;
+    /// Backup the hostfile and write the entries to it. This does not
+    /// escalate privileges, the caller needs permission to write the file.
+    ///
+    /// When the hostfile is `STDIO` the entries are written to stdout without
+    /// a backup.
+    pub fn write(&self) -> Result<(), ApplicationError> {
+        if self.is_stdio() {
+            return self.write_to(&mut io::stdout().lock());
+        }
 
-        let display = path.display();
+        self.backup()?;
 
+        let path = Path::new(&self.filename);
         let mut file = match File::create(path) {
-            Err(why) => panic!("couldn't create {}: {}", display, why),
+            Err(why) => {
+                return Err(ApplicationError::HostFileUnwritable(format!(
+                    "couldn't create {}: {}",
+                    path.display(),
+                    why
+                )))
+            }
             Ok(file) => file,
         };
 
-        for item in self.entries.as_ref().unwrap() {
-            if let Err(why) = writeln!(file, "{}", item) {
-                panic!("couldn't write to {}: {}", display, why)
+        self.write_to(&mut file)
+    }
+
+    /// Write the entries, one per line and aligned as set in `align`, to `out`.
+    /// The line ending and final newline are the ones of the file that was
+    /// read, unless `line_ending` or `final_newline` is set.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> Result<(), ApplicationError> {
+        let entries = self.entries.as_deref().unwrap_or(&[]);
+        let ending = match (self.line_ending, self.flavor) {
+            (Some(ending), _) => ending,
+            (None, Flavor::Windows) => LineEnding::Crlf,
+            (None, Flavor::Unix) => self.read_ending,
+        }
+        .as_str();
+        let lines: Vec<Vec<u8>> = self.written(entries).into_iter().flatten().collect();
+
+        let mut content = vec![];
+        for (idx, line) in lines.iter().enumerate() {
+            if idx > 0 {
+                content.extend_from_slice(ending.as_bytes());
             }
+            content.extend_from_slice(line);
         }
-        Ok(())
+        if !lines.is_empty() && self.final_newline.unwrap_or(self.read_final_newline) {
+            content.extend_from_slice(ending.as_bytes());
+        }
+
+        out.write_all(&content).map_err(|why| {
+            ApplicationError::HostFileUnwritable(format!(
+                "couldn't write to {}: {}",
+                self.filename, why
+            ))
+        })
     }
 
+    /// The limits on the lines, `Flavor::Windows` reads at most
+    /// `WINDOWS_MAX_NAMES` names from a line
+    pub fn limits(&self) -> Limits {
+        let names = match self.flavor {
+            Flavor::Unix => self.max_names,
+            Flavor::Windows => Some(
+                self.max_names
+                    .map_or(WINDOWS_MAX_NAMES, |max| max.min(WINDOWS_MAX_NAMES)),
+            ),
+        };
+        Limits {
+            names,
+            line_length: self.max_line_length,
+        }
+    }
+
+    /// The lines every entry is written as: the lines it was read from while
+    /// it did not change, otherwise rendered as set by `align`. With `align`
+    /// set the lines of IP addresses are rendered, unless they are not valid
+    /// UTF-8. A rendered comment keeps the bytes it was read with.
+    fn written(&self, entries: &[HostEntry]) -> Vec<Vec<Vec<u8>>> {
+        let limits = self.limits();
+        let mut rendered = format::render(entries, self.align, &limits).into_iter();
+        entries
+            .iter()
+            .zip(self.raw_lines(entries))
+            .map(|(entry, raw)| {
+                // an entry is rendered as one or more lines
+                let mut lines: Vec<Vec<u8>> = rendered
+                    .by_ref()
+                    .take(entry.split(&limits).len())
+                    .map(String::into_bytes)
+                    .collect();
+                if let Some(raw) = raw.filter(|raw| raw.len() == lines.len()) {
+                    if self.align == Align::None
+                        || entry.ip.is_none()
+                        || raw.iter().any(|l| std::str::from_utf8(l).is_err())
+                    {
+                        return raw.to_vec();
+                    }
+                }
+                if let (Some(comment), Some(first)) = (&entry.comment, lines.first_mut()) {
+                    if let Some(bytes) = self.comment_bytes(comment) {
+                        if first.ends_with(comment.as_bytes()) {
+                            first.truncate(first.len() - comment.len());
+                            first.extend_from_slice(bytes);
+                        }
+                    }
+                }
+                lines
+            })
+            .collect()
+    }
+
+    /// The bytes of `comment` as it was read, when they are not valid UTF-8
+    fn comment_bytes(&self, comment: &str) -> Option<&[u8]> {
+        if !comment.contains(char::REPLACEMENT_CHARACTER) {
+            return None;
+        }
+        self.raw
+            .iter()
+            .filter(|(e, _)| e.comment.as_deref() == Some(comment))
+            .find_map(|(_, lines)| {
+                // a comment is on the first line of an entry
+                let line = lines.first()?;
+                let start = line.iter().position(|b| *b == b'#')? + 1;
+                let bytes = trim_ascii(&line[start..]);
+                Some(bytes).filter(|b| String::from_utf8_lossy(b) == comment)
+            })
+    }
+
+    /// Copy the hostfile to `<file>.bak`, rotating older backups to
+    /// `<file>.bak.1` and up until `backup_retention` backups are kept.
     pub fn backup(&self) -> Result<(), ApplicationError> {
-        if let Err(e) = fs::copy(&self.filename, format!("{}.bak", self.filename)) {
-            return Err(ApplicationError::BackupFileWriteFailed(e.to_string()));
+        if self.backup_retention == 0 {
+            return Ok(());
+        }
+        let failed = |e: io::Error| ApplicationError::BackupFileWriteFailed(e.to_string());
+
+        if let Some(dir) = &self.backup_dir {
+            fs::create_dir_all(dir).map_err(failed)?;
+        }
+        for n in (1..self.backup_retention).rev() {
+            let older = self.backup_path(n - 1);
+            if older.exists() {
+                fs::rename(older, self.backup_path(n)).map_err(failed)?;
+            }
         }
+        fs::copy(&self.filename, self.backup_path(0)).map_err(failed)?;
 
         Ok(())
     }
 
+    /// Path of the `n`th backup, `0` being the most recent one. In
+    /// `backup_dir` the backup is named after the whole path of the hostfile,
+    /// so hostfiles with the same name do not share their backups.
+    pub fn backup_path(&self, n: usize) -> PathBuf {
+        let path = Path::new(&self.filename);
+        let name = match (&self.backup_dir, path.file_name()) {
+            (Some(_), _) => path_name(path),
+            (None, Some(f)) => f.to_string_lossy().to_string(),
+            (None, None) => self.filename.clone(),
+        };
+        let name = match n {
+            0 => format!("{}.bak", name),
+            _ => format!("{}.bak.{}", name, n),
+        };
+
+        match (&self.backup_dir, path.parent()) {
+            (Some(dir), _) => dir.join(name),
+            (None, Some(dir)) => dir.join(name),
+            (None, None) => PathBuf::from(name),
+        }
+    }
+
+    /// Read the entries from the file, or stdin when the hostfile is `STDIO`
     pub fn parse(&mut self) -> Result<(), ApplicationError> {
-        //println!("Reading file {}", self.filename);
-        match read_lines(&self.filename) {
-            Ok(lines) => {
-                self.entries = lines
-                    .map(|l| match l.unwrap_or_else(|_e| "".to_string()).parse() {
-                        Ok(s) => Some(s),
-                        _ => None,
+        if self.is_stdio() {
+            return self.parse_from(io::stdin().lock());
+        }
+
+        match File::open(&self.filename) {
+            Ok(file) => self.parse_from(file),
+            Err(e) => Err(ApplicationError::HostFileUnreadable(format!(
+                "{}: {}",
+                self.filename, e
+            ))),
+        }
+    }
+
+    /// Read the entries from `reader`, replacing any existing entries. The
+    /// lines of an entry that `write_to` split, as set by `max_names`,
+    /// `max_line_length` and `flavor`, are joined into one entry. The bytes
+    /// of lines that are not valid UTF-8 are kept for `write_to`, the entries
+    /// have `U+FFFD` in their place. The line ending used by most lines and
+    /// whether the last line ends with one are remembered for `write_to`.
+    pub fn parse_from<R: Read>(&mut self, reader: R) -> Result<(), ApplicationError> {
+        let mut lines = vec![];
+        let mut source = vec![];
+        let mut bytes_read = vec![];
+        let (mut lf, mut crlf) = (0, 0);
+        let mut final_newline = true;
+
+        let mut reader = BufReader::new(reader);
+        let mut bytes = vec![];
+        loop {
+            bytes.clear();
+            if reader.read_until(b'\n', &mut bytes)? == 0 {
+                break;
+            }
+            final_newline = bytes.last() == Some(&b'\n');
+            if final_newline {
+                bytes.pop();
+            }
+            if bytes.last() == Some(&b'\r') {
+                bytes.pop();
+                crlf += 1;
+            } else if final_newline {
+                lf += 1;
+            }
+
+            let line = String::from_utf8_lossy(&bytes).to_string();
+            lines.push(line.parse().unwrap_or_else(|_| HostEntry::empty()));
+            bytes_read.push(bytes.clone());
+            source.push(line);
+        }
+
+        let mut entries = vec![];
+        let mut read = vec![];
+        let mut raw = vec![];
+        let mut start = 0;
+        for (entry, count) in format::join(&lines, &self.limits()) {
+            let span = start..start + count;
+            raw.push((entry.clone(), bytes_read[span.clone()].to_vec()));
+            read.push((entry.clone(), span));
+            entries.push(entry);
+            start += count;
+        }
+        self.entries = Some(entries);
+        self.source = source;
+        self.read = read;
+        self.raw = raw;
+        self.read_ending = if crlf > lf {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        };
+        self.read_final_newline = final_newline;
+        Ok(())
+    }
+
+    /// The (1-based) numbers of the lines with bytes that are not valid UTF-8
+    pub fn non_utf8_lines(&self) -> Vec<usize> {
+        let entries = self.entries.as_deref().unwrap_or(&[]);
+        let mut lines = vec![];
+        for (span, raw) in self.spans().into_iter().zip(self.raw_lines(entries)) {
+            for (idx, bytes) in span.zip(raw.into_iter().flatten()) {
+                if std::str::from_utf8(bytes).is_err() {
+                    lines.push(idx + 1);
+                }
+            }
+        }
+        lines
+    }
+
+    /// The lines as read for every entry that did not change since
+    fn raw_lines(&self, entries: &[HostEntry]) -> Vec<Option<&[Vec<u8>]>> {
+        let mut used = vec![false; self.raw.len()];
+        let mut next = 0;
+        entries
+            .iter()
+            .map(|e| {
+                // the entries are mostly in the order they were read
+                let p = (next..self.raw.len())
+                    .chain(0..next)
+                    .find(|&p| !used[p] && self.raw[p].0 == *e)?;
+                used[p] = true;
+                next = p + 1;
+                Some(self.raw[p].1.as_slice())
+            })
+            .collect()
+    }
+
+    /// Replace the entries by the ones of `other`, with the lines of `other`
+    /// as they were read
+    pub fn take_entries(&mut self, other: HostFile) {
+        self.entries = other.entries;
+        self.raw = other.raw;
+    }
+
+    /// The lines as they were read by `parse`, while the entries did not
+    /// change since
+    fn read_lines(&self) -> Option<&[String]> {
+        let entries = self.entries.as_deref().unwrap_or(&[]);
+        let unchanged = entries.len() == self.read.len()
+            && entries.iter().zip(&self.read).all(|(e, (r, _))| e == r);
+        Some(self.source.as_slice()).filter(|_| unchanged)
+    }
+
+    /// The indices of the lines of every entry: the lines it was read from
+    /// while the entries did not change since `parse`, otherwise the lines it
+    /// is written as
+    fn spans(&self) -> Vec<Range<usize>> {
+        if self.read_lines().is_some() {
+            return self.read.iter().map(|(_, span)| span.clone()).collect();
+        }
+        let mut start = 0;
+        self.written(self.entries.as_deref().unwrap_or(&[]))
+            .iter()
+            .map(|lines| {
+                start += lines.len();
+                start - lines.len()..start
+            })
+            .collect()
+    }
+
+    /// The (1-based) number of the first line of every entry, in the file as
+    /// read while the entries did not change since `parse`, otherwise as it
+    /// is written
+    pub fn line_numbers(&self) -> Vec<usize> {
+        self.spans().iter().map(|span| span.start + 1).collect()
+    }
+
+    /// All entries that contain `hostname`, with their (1-based) line number,
+    /// in the order a resolver reads them
+    pub fn lookup(&self, hostname: &str) -> Vec<(usize, &HostEntry)> {
+        self.line_numbers()
+            .into_iter()
+            .zip(self.entries.iter().flatten())
+            .filter(|(_, e)| e.ip.is_some() && e.has_name(hostname))
+            .collect()
+    }
+
+    /// The names of every IP address in `network`, in the order the addresses
+    /// first appear in the file
+    pub fn who(&self, network: &Network) -> Vec<Reverse> {
+        let mut found: Vec<Reverse> = vec![];
+        let numbers = self.line_numbers();
+        for (line, entry) in numbers.into_iter().zip(self.entries.iter().flatten()) {
+            let ip = match entry.ip {
+                Some(ip) if network.contains(&ip) => ip,
+                _ => continue,
+            };
+            let names: Vec<String> = entry.names().into_iter().map(String::from).collect();
+            match found.iter_mut().find(|r| r.ip == ip) {
+                Some(r) => {
+                    r.lines.push(line);
+                    for name in names {
+                        if !r.names.contains(&name) {
+                            r.names.push(name);
+                        }
+                    }
+                }
+                None => {
+                    let mut unique: Vec<String> = vec![];
+                    for name in &names {
+                        if !unique.contains(name) {
+                            unique.push(name.clone());
+                        }
+                    }
+                    found.push(Reverse {
+                        ip,
+                        lines: vec![line],
+                        names: unique,
+                        answer: names,
                     })
-                    .collect();
-                Ok(())
+                }
             }
-            Err(e) => Err(ApplicationError::FileNotParseable(e.to_string())),
         }
+        found
     }
 
-    pub(crate) fn remove_ip(&mut self, entry: String) -> Modifications {
-        let mut mods = Modifications::new();
+    /// Find the names that are shadowed: an earlier line has the same name for
+    /// the same address family, and resolvers use the first matching line.
+    pub fn shadowed(&self) -> Vec<Shadowed> {
+        let mut shadowed = vec![];
+        let mut seen: Vec<(&str, IpAddr, usize)> = vec![];
 
-        let ip: IpAddr = match entry.parse() {
-            Ok(x) => x,
-            Err(e) => {
-                eprintln!("Invalid IP address given: {}", e);
-                return mods;
+        let numbers = self.line_numbers();
+        for (line, entry) in numbers.into_iter().zip(self.entries.iter().flatten()) {
+            let ip = match entry.ip {
+                Some(ip) => ip,
+                None => continue,
+            };
+            for name in entry.names() {
+                // a name can have both an IPv4 and IPv6 address
+                match seen
+                    .iter()
+                    .find(|(n, i, _)| *n == name && i.is_ipv4() == ip.is_ipv4())
+                {
+                    Some((_, by_ip, by_line)) => shadowed.push(Shadowed {
+                        line,
+                        name: String::from(name),
+                        ip,
+                        by_line: *by_line,
+                        by_ip: *by_ip,
+                    }),
+                    None => seen.push((name, ip, line)),
+                }
+            }
+        }
+        shadowed
+    }
+
+    /// Check every entry for problems that would break name resolution:
+    /// invalid IP addresses, malformed hostnames and hostnames that are
+    /// shadowed by an earlier line with a different IP address. A hostname
+    /// listed twice for the same IP is only a warning.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = vec![];
+        let spans = self.spans();
+
+        for (span, entry) in spans.iter().zip(self.entries.iter().flatten()) {
+            let line = span.start + 1;
+            if entry.name.is_none() {
+                continue;
             }
+
+            if entry.ip.is_none() {
+                let mut issue = Issue::error(
+                    line,
+                    String::from("the line does not start with a valid IP address"),
+                );
+                if let Some(ip) = self
+                    .read_lines()
+                    .and_then(|lines| lines[span.start].split_whitespace().next())
+                {
+                    issue = issue.with_text(ip);
+                }
+                issues.push(issue);
+                continue;
+            }
+
+            for name in entry.invalid_names() {
+                issues.push(
+                    Issue::error(line, format!("malformed hostname `{}`", name)).with_text(name),
+                );
+            }
+
+            // loopback and multicast lines are not about a host
+            let special = matches!(entry.ip, Some(ip) if ip.is_loopback() || ip.is_multicast());
+            if let (Some(name), Some(fqdn), false) = (&entry.name, entry.fqdn_alias(), special) {
+                issues.push(
+                    Issue::warning(
+                        line,
+                        format!(
+                            "the canonical name `{}` is not fully qualified, reverse lookups return it instead of `{}`",
+                            name, fqdn
+                        ),
+                    )
+                    .with_text(name),
+                );
+            }
+        }
+
+        for s in self.shadowed() {
+            if s.ip != s.by_ip {
+                issues.push(
+                    Issue::error(
+                        s.line,
+                        format!(
+                        "`{}` is shadowed by line {}, which maps it to {}; {} is never returned",
+                        s.name, s.by_line, s.by_ip, s.ip
+                    ),
+                    )
+                    .with_text(&s.name),
+                );
+            } else {
+                issues.push(
+                    Issue::warning(
+                        s.line,
+                        format!("`{}` is already listed on line {}", s.name, s.by_line),
+                    )
+                    .with_text(&s.name),
+                );
+            }
+        }
+
+        let limits = self.limits();
+        for (span, entry) in spans.iter().zip(self.entries.iter().flatten()) {
+            let lines = entry.split(&limits).len();
+            // an entry that `write_to` split is read from as many lines
+            let read_from = self.read_lines().map_or(1, |_| span.len());
+            if lines > read_from {
+                issues.push(Issue::warning(
+                    span.start + 1,
+                    format!(
+                        "the line has more names or characters than allowed, it is written as {} lines",
+                        lines
+                    ),
+                ));
+            }
+        }
+
+        match self.flavor {
+            Flavor::Unix => issues.extend(self.loopback_issues()),
+            Flavor::Windows => issues.extend(self.windows_issues()),
+        }
+
+        for line in self.non_utf8_lines() {
+            issues.push(Issue::warning(
+                line,
+                String::from("the line is not valid UTF-8, its bytes are kept as they are"),
+            ));
+        }
+
+        issues.sort_by_key(|i| i.line);
+        issues
+    }
+
+    /// A missing `::1 localhost` line, for a file that maps `localhost` to an
+    /// IPv4 address
+    fn loopback_issues(&self) -> Vec<Issue> {
+        let mut issues = vec![];
+        let entries = self.entries.as_deref().unwrap_or(&[]);
+        let v4 = match entries.iter().position(|e| {
+            matches!(e.ip, Some(ip) if ip.is_ipv4() && ip.is_loopback()) && e.has_name("localhost")
+        }) {
+            Some(idx) => self.line_numbers()[idx],
+            None => return issues,
+        };
+        let localhost = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        if !entries
+            .iter()
+            .any(|e| e.has_ip(&localhost) && e.has_name("localhost"))
+        {
+            issues.push(
+                Issue::warning(
+                    v4,
+                    String::from(
+                        "there is no `::1 localhost` line, programs using IPv6 can not resolve localhost",
+                    ),
+                )
+                .with_text("localhost"),
+            );
+        }
+        issues
+    }
+
+    /// Lines that do not follow the conventions of the Windows hostfile
+    fn windows_issues(&self) -> Vec<Issue> {
+        let mut issues = vec![];
+        let numbers = self.line_numbers();
+        for (line, entry) in numbers.into_iter().zip(self.entries.iter().flatten()) {
+            if entry.ip.is_none() {
+                continue;
+            }
+            if entry.names().len() > 1 {
+                issues.push(Issue::warning(
+                    line,
+                    String::from("Windows recommends a single hostname per line"),
+                ));
+            }
+            if entry.has_name("localhost") {
+                issues.push(
+                    Issue::warning(
+                        line,
+                        String::from(
+                            "Windows resolves localhost itself, the stock hosts file has this line commented out",
+                        ),
+                    )
+                    .with_text("localhost"),
+                );
+            }
+        }
+        issues
+    }
+
+    /// The issues found by `validate` with the file, line and columns they
+    /// were found at
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let lines: Vec<String> = match self.read_lines() {
+            Some(lines) => lines.to_vec(),
+            None => self
+                .written(self.entries.as_deref().unwrap_or(&[]))
+                .into_iter()
+                .flatten()
+                .map(|l| String::from_utf8_lossy(&l).to_string())
+                .collect(),
+        };
+        self.validate()
+            .into_iter()
+            .map(|issue| {
+                let source = lines[issue.line - 1].clone();
+                let columns = issue
+                    .text
+                    .as_deref()
+                    .and_then(|t| token_columns(&source, t))
+                    .unwrap_or_else(|| {
+                        let start = source.chars().take_while(|c| c.is_whitespace()).count();
+                        start..source.trim_end().chars().count()
+                    });
+                Diagnostic {
+                    file: self.filename.clone(),
+                    line: issue.line,
+                    columns,
+                    source,
+                    severity: issue.severity,
+                    message: issue.message,
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn remove_ip(&mut self, entry: String) -> Result<Modifications, ApplicationError> {
+        let mut mods = Modifications::new();
+
+        let (ip, zone) = match parse_address(&entry) {
+            Some(x) => x,
+            None => return Err(ApplicationError::IpAddressConversion()),
         };
 
         if self.entries.is_some() {
@@ -94,7 +746,7 @@ impl HostFile {
             self.entries = Some(
                 en.into_iter()
                     .filter(|he| {
-                        if he.ip.is_some() && he.ip.unwrap() == ip {
+                        if he.has_address(&ip, zone.as_deref()) {
                             mods.removed_entries += 1;
                             return false;
                         }
@@ -102,14 +754,11 @@ impl HostFile {
                     })
                     .collect::<Vec<_>>(),
             );
-            mods
-        } else {
-            eprintln!("No entries to delete");
-            mods
         }
+        Ok(mods)
     }
 
-    pub(crate) fn remove_name(&mut self, name: String) -> Modifications {
+    pub(crate) fn remove_name(&mut self, name: String, family: Option<Family>) -> Modifications {
         let mut mods = Modifications::new();
         // if the name is the `name` and no aliasses, remove the entry
 
@@ -118,7 +767,9 @@ impl HostFile {
 
             let mut updated: Vec<HostEntry> = vec![];
             for mut entry in en {
-                if !entry.can_delete(name.as_str()) {
+                if matches!(family, Some(f) if !entry.is_family(f)) {
+                    updated.push(entry);
+                } else if !entry.can_delete(name.as_str()) {
                     let (m, e) = entry.remove_hostname(name.as_str());
                     mods.updated_entries += m.updated_entries;
                     mods.removed_entries += m.removed_entries;
@@ -129,31 +780,42 @@ impl HostFile {
             }
 
             self.entries = Some(updated);
-            mods
-        } else {
-            eprintln!("No entries to delete");
-            mods
         }
-
-        // else the entry needs to be update, if it is there
+        mods
     }
 
-    /// Add a new entry to the hosts file
-    pub(crate) fn add(
+    /// Add a new entry to the hosts file. Without an IP address the hostname
+    /// is added as an alias to the entry of its parent domain.
+    pub fn add(
         &mut self,
         hostname: String,
         ip: Option<String>,
     ) -> Result<Modifications, crate::errors::ApplicationError> {
         let mut mods = Modifications::new();
 
-        let ip_address: Option<IpAddr> = match ip {
-            Some(x) => match x.parse() {
-                Ok(y) => Some(y),
-                Err(_e) => return Err(ApplicationError::IpAddressConversion()),
+        let (ip_address, zone) = match ip {
+            Some(x) => match parse_address(&x) {
+                Some((y, zone)) => (Some(y), zone),
+                None => return Err(ApplicationError::IpAddressConversion()),
             },
-            _ => None,
+            _ => (None, None),
         };
 
+        // a hostname can have an IPv4 and an IPv6 address
+        if let Some(ip_a) = ip_address {
+            if let Some(e) = self.entries.iter().flatten().find(|e| {
+                e.has_name(&hostname)
+                    && e.is_family(Family::of(&ip_a))
+                    && !e.has_address(&ip_a, zone.as_deref())
+            }) {
+                return Err(ApplicationError::HostnameAlreadyInUse(format!("{}", e)));
+            }
+        }
+
+        if self.flavor == Flavor::Windows {
+            return self.add_line(hostname, ip_address, zone);
+        }
+
         // if IP address is given, find a matching hostentry to add a alias
         //    no ip? add new entry
         // if only a name is given, find a HostEntry already serving a tld
@@ -162,7 +824,7 @@ impl HostFile {
             for item in self.entries.iter_mut().flatten() {
                 let i = item;
 
-                if i.has_ip(&ip_a) && !i.has_name(hostname.as_str()) {
+                if i.has_address(&ip_a, zone.as_deref()) && !i.has_name(hostname.as_str()) {
                     if i.can_hostname_resolve_domain(hostname.as_str()) {
                         i.switch_name_with_alias(hostname.as_str());
                     } else {
@@ -170,15 +832,15 @@ impl HostFile {
                     }
                     mods.updated_entries += 1;
                     return Ok(mods);
-                } else if i.has_ip(&ip_a) {
-                    return Err(ApplicationError::IpAlreadyInUse(format!("{}", i)));
-                } else if !i.has_ip(&ip_a) && i.has_name(hostname.as_str()) {
-                    return Err(ApplicationError::HostnameAlreadyInUse(format!("{}", i)));
+                } else if i.has_address(&ip_a, zone.as_deref()) {
+                    // the mapping exists, nothing is modified
+                    return Ok(mods);
                 }
             }
             mods.added_entries += 1;
             self.add_host_entry(HostEntry {
                 ip: ip_address,
+                zone,
                 name: Some(hostname),
                 comment: None,
                 aliasses: None,
@@ -197,8 +859,7 @@ impl HostFile {
                     mods.updated_entries += 1;
                     return Ok(mods);
                 } else if i.has_name(hostname.as_str()) {
-                    eprintln!("Hostname already exists in the hostfile");
-                    // It already exists
+                    // It already exists, nothing is modified
                     return Ok(mods);
                 }
             }
@@ -207,73 +868,391 @@ impl HostFile {
         }
     }
 
-    /// Replace the IP address for a record, will include all of the aliasses as well
-    pub(crate) fn replace(
+    /// Add the hostname on a line of its own, as Windows recommends. Without
+    /// an IP address the one of its parent domain is used.
+    fn add_line(
         &mut self,
         hostname: String,
-        ip: Option<String>,
+        ip: Option<IpAddr>,
+        zone: Option<String>,
     ) -> Result<Modifications, ApplicationError> {
-        let mut mods = Modifications::new();
-        let ip_address: Option<IpAddr> = match ip {
-            Some(x) => match x.parse() {
-                Ok(y) => Some(y),
-                Err(_e) => return Err(ApplicationError::IpAddressConversion()),
+        let entries = self.entries.get_or_insert_with(Vec::new);
+
+        // `add` checked the hostname is not in use by another IP address
+        if entries.iter().any(|e| {
+            e.has_name(&hostname)
+                && match ip {
+                    Some(ip) => e.has_address(&ip, zone.as_deref()),
+                    None => e.ip.is_some(),
+                }
+        }) {
+            // the mapping exists, nothing is modified
+            return Ok(Modifications::new());
+        }
+
+        let (ip, zone) = match ip {
+            Some(ip) => (ip, zone),
+            None => match entries
+                .iter()
+                .find(|e| e.ip.is_some() && e.can_resolve_host(&hostname))
+            {
+                Some(parent) => (parent.ip.unwrap(), parent.zone.clone()),
+                None => return Err(ApplicationError::NoParentDomain()),
             },
-            _ => None,
         };
+        Ok(self.insert_name(hostname, ip, zone))
+    }
 
-        for item in self.entries.iter_mut().flatten() {
-            let i = item;
+    /// Add the hostname for the IP address, which does not have it yet. For
+    /// `Flavor::Windows` it is a new line below the last line of the IP
+    /// address, otherwise an alias on the first line of the IP address. A new
+    /// entry is added when the IP address is not in the file.
+    fn insert_name(&mut self, hostname: String, ip: IpAddr, zone: Option<String>) -> Modifications {
+        let mut mods = Modifications::new();
+        let entries = self.entries.get_or_insert_with(Vec::new);
+        let entry = HostEntry {
+            ip: Some(ip),
+            zone,
+            name: Some(hostname),
+            aliasses: None,
+            comment: None,
+        };
 
-            if i.name.is_some() && i.name.as_ref().unwrap() == hostname.as_str() {
-                i.ip = ip_address;
-                mods.updated_entries += 1;
-                return Ok(mods);
+        if self.flavor == Flavor::Windows {
+            if let Some(last) = entries
+                .iter()
+                .rposition(|e| e.has_address(&ip, entry.zone.as_deref()))
+            {
+                entries.insert(last + 1, entry);
+                mods.added_entries += 1;
+                return mods;
+            }
+        } else if let Some(existing) = entries
+            .iter_mut()
+            .find(|e| e.has_address(&ip, entry.zone.as_deref()))
+        {
+            existing.add_alias(entry.name.as_ref().unwrap());
+            mods.updated_entries += 1;
+            return mods;
+        }
+        self.add_host_entry(entry);
+        mods.added_entries += 1;
+        mods
+    }
+
+    /// Add the hostname for an IPv4 and an IPv6 address, in either order. The
+    /// hostname can only be in use by another IP address of the other family.
+    pub fn add_dual_stack(
+        &mut self,
+        hostname: String,
+        ip: String,
+        other: String,
+    ) -> Result<Modifications, ApplicationError> {
+        let mut mods = Modifications::new();
+
+        let mut addresses = vec![];
+        for address in [ip, other] {
+            match parse_address(&address) {
+                Some(a) => addresses.push(a),
+                None => return Err(ApplicationError::IpAddressConversion()),
+            }
+        }
+        if addresses[0].0.is_ipv4() == addresses[1].0.is_ipv4() {
+            return Err(ApplicationError::FamilyMismatch(format!(
+                "--dual-stack takes one IPv4 and one IPv6 address, {} and {} are both IPv{}",
+                addresses[0].0,
+                addresses[1].0,
+                if addresses[0].0.is_ipv4() { 4 } else { 6 }
+            )));
+        }
+
+        let mut missing = vec![];
+        for (ip, zone) in addresses {
+            let existing = self
+                .entries
+                .iter()
+                .flatten()
+                .find(|e| e.has_name(&hostname) && e.is_family(Family::of(&ip)));
+            match existing {
+                Some(e) if e.has_address(&ip, zone.as_deref()) => {}
+                Some(e) => return Err(ApplicationError::HostnameAlreadyInUse(format!("{}", e))),
+                None => missing.push((ip, zone)),
             }
         }
+        for (ip, zone) in missing {
+            mods.merge(self.insert_name(hostname.clone(), ip, zone));
+        }
         Ok(mods)
     }
 
-    /// Color print the hosts file
-    pub(crate) fn show(&self) -> Result<Modifications, ApplicationError> {
-        let mut out = std::io::stdout();
-        if self.entries.is_some() {
-            for item in self.entries.as_ref().unwrap() {
-                if let Err(e) = item.color_print(&mut out) {
-                    eprintln!("Could not print to stdout.... {}", e);
+    /// Add the hostname for the IP address above every line that has the
+    /// hostname for the same address family, so it is the mapping resolvers
+    /// use. The lines that had the hostname are kept, but are shadowed.
+    pub fn add_first(
+        &mut self,
+        hostname: String,
+        ip: String,
+    ) -> Result<Modifications, ApplicationError> {
+        let mut mods = Modifications::new();
+        let (ip_address, zone) = match parse_address(&ip) {
+            Some(y) => y,
+            None => return Err(ApplicationError::IpAddressConversion()),
+        };
+
+        let first =
+            self.entries.iter().flatten().position(|e| {
+                e.has_name(hostname.as_str()) && e.is_family(Family::of(&ip_address))
+            });
+        match first {
+            None => self.add(hostname, Some(ip)),
+            Some(f) => {
+                let entries = self.entries.get_or_insert_with(Vec::new);
+                if !entries[f].has_address(&ip_address, zone.as_deref()) {
+                    entries.insert(
+                        f,
+                        HostEntry {
+                            ip: Some(ip_address),
+                            zone,
+                            name: Some(hostname),
+                            aliasses: None,
+                            comment: None,
+                        },
+                    );
+                    mods.added_entries += 1;
                 }
+                Ok(mods)
             }
         }
-        Ok(Modifications::new())
     }
 
-    /// Delete an name or IP address from the hostsfile
-    pub(crate) fn delete(&mut self, entry: String) -> Result<Modifications, ApplicationError> {
+    /// Replace the IP address for a record, will include all of the aliasses
+    /// as well. The entry of the `family`, or else of the family of the new
+    /// IP address, is replaced so a dual-stack host keeps its other address.
+    /// Without `family` a hostname that only has an address of the other
+    /// family gets the new one. The hostname can not already have another
+    /// line of the family of the new IP address.
+    ///
+    /// With `only_this_name` the other names keep the old IP address, the
+    /// hostname is moved to a new line below it.
+    pub fn replace(
+        &mut self,
+        hostname: String,
+        ip: String,
+        family: Option<Family>,
+        only_this_name: bool,
+    ) -> Result<Modifications, ApplicationError> {
         let mut mods = Modifications::new();
-        let is_ip = match entry.parse::<IpAddr>() {
-            Ok(_e) => true,
-            Err(_e) => false,
+        let (ip_address, zone) = match parse_address(&ip) {
+            Some(address) => address,
+            None => return Err(ApplicationError::IpAddressConversion()),
+        };
+
+        let entries = self.entries.get_or_insert_with(Vec::new);
+        let find = |family: Option<Family>| {
+            entries.iter().position(|e| {
+                e.ip.is_some() && e.has_name(&hostname) && family.iter().all(|f| e.is_family(*f))
+            })
         };
+        let found = match family {
+            Some(f) => find(Some(f)),
+            None => find(Some(Family::of(&ip_address))).or_else(|| find(None)),
+        };
+        let idx = match found {
+            Some(idx) => idx,
+            None => return Err(ApplicationError::HostnameDoesNotExist(hostname)),
+        };
+        // like `add`, a hostname has one address of each family
+        if let Some(other) = entries.iter().enumerate().find(|(i, e)| {
+            *i != idx && e.has_name(&hostname) && e.is_family(Family::of(&ip_address))
+        }) {
+            return Err(ApplicationError::HostnameAlreadyInUse(format!(
+                "{}",
+                other.1
+            )));
+        }
+
+        if !only_this_name || entries[idx].names().len() == 1 {
+            entries[idx].ip = Some(ip_address);
+            entries[idx].zone = zone;
+            mods.updated_entries += 1;
+            return Ok(mods);
+        }
+
+        let (_, rest) = entries[idx].remove_hostname(&hostname);
+        entries[idx] = rest;
+        entries.insert(
+            idx + 1,
+            HostEntry {
+                ip: Some(ip_address),
+                zone,
+                name: Some(hostname),
+                aliasses: None,
+                comment: None,
+            },
+        );
+        mods.updated_entries += 1;
+        mods.added_entries += 1;
+        Ok(mods)
+    }
+
+    /// Color print the hosts file to `out`
+    pub fn show<W: Write>(
+        &self,
+        out: &mut W,
+        theme: &Theme,
+    ) -> Result<Modifications, ApplicationError> {
+        self.show_with(out, theme, &ShowOptions::default())
+    }
 
-        if is_ip {
-            let m = self.remove_ip(entry);
+    /// Color print the hosts file to `out`, sorted, grouped and filtered as
+    /// set in `options`. Every group starts with its title as a comment.
+    pub fn show_with<W: Write>(
+        &self,
+        out: &mut W,
+        theme: &Theme,
+        options: &ShowOptions,
+    ) -> Result<Modifications, ApplicationError> {
+        let entries = self.entries.as_deref().unwrap_or(&[]);
+        for (idx, (title, members)) in options.groups(entries).iter().enumerate() {
+            if idx > 0 {
+                writeln!(out)?;
+            }
+            if let Some(t) = title {
+                writeln!(out, "{}# {}{}", theme.comment, t, theme.reset)?;
+            }
+            for item in members {
+                item.color_print(out, theme)?;
+            }
+        }
+        Ok(Modifications::new())
+    }
+
+    /// Delete an name or IP address from the hostsfile. With `family` a
+    /// name is only removed from the entries of that address family, an IP
+    /// address already has a family.
+    pub fn delete(
+        &mut self,
+        entry: String,
+        family: Option<Family>,
+    ) -> Result<Modifications, ApplicationError> {
+        let mut mods = Modifications::new();
+
+        if parse_address(&entry).is_some() {
+            if family.is_some() {
+                return Err(ApplicationError::UnknownOption(format!(
+                    "--family with the IP address `{}`, it only applies to a hostname",
+                    entry
+                )));
+            }
+            let m = self.remove_ip(entry)?;
             mods.merge(m);
         } else {
-            let m = self.remove_name(entry);
+            let m = self.remove_name(entry, family);
             mods.merge(m);
         }
 
         Ok(mods)
     }
 
-    /// Add an alias to the hostname, there is no check on overlap so it is not limited to subdomains
-    pub(crate) fn alias(
+    /// Add subdomains of `domain` as aliases to the entry of `domain`, for a
+    /// dual-stack domain to its first line of either family. A subdomain is a
+    /// label (`api`) or the full hostname (`api.box.htb`), subdomains that
+    /// already have the address of `domain` are skipped. A subdomain can only
+    /// be in use by another IP address of a family `domain` does not have.
+    pub fn add_subdomains<S: AsRef<str>>(
+        &mut self,
+        domain: &str,
+        subdomains: &[S],
+    ) -> Result<Modifications, ApplicationError> {
+        let mut mods = Modifications::new();
+
+        let mut hostnames: Vec<String> = vec![];
+        for sub in subdomains {
+            let sub = sub.as_ref().trim().trim_end_matches('.');
+            if sub.is_empty() {
+                continue;
+            }
+            let hostname = if sub == domain || sub.ends_with(&format!(".{}", domain)) {
+                String::from(sub)
+            } else {
+                format!("{}.{}", sub, domain)
+            };
+            if !is_valid_hostname(&hostname) {
+                return Err(ApplicationError::InvalidHostname(hostname));
+            }
+            if !hostnames.contains(&hostname) {
+                hostnames.push(hostname);
+            }
+        }
+
+        let entries = self.entries.get_or_insert_with(Vec::new);
+        // resolvers use the first line of each family
+        let targets: Vec<usize> = [Family::V4, Family::V6]
+            .iter()
+            .filter_map(|f| {
+                entries
+                    .iter()
+                    .position(|e| e.is_family(*f) && e.has_name(domain))
+            })
+            .collect();
+        if targets.is_empty() {
+            return Err(ApplicationError::HostnameDoesNotExist(String::from(domain)));
+        }
+
+        let mut missing: Vec<(usize, &String)> = vec![];
+        for &idx in &targets {
+            let (ip, zone) = (entries[idx].ip.unwrap(), entries[idx].zone.as_deref());
+            for hostname in &hostnames {
+                match entries
+                    .iter()
+                    .find(|e| e.has_name(hostname) && e.is_family(Family::of(&ip)))
+                {
+                    Some(e) if e.has_address(&ip, zone) => {}
+                    Some(e) => {
+                        return Err(ApplicationError::HostnameAlreadyInUse(format!("{}", e)))
+                    }
+                    None => missing.push((idx, hostname)),
+                }
+            }
+        }
+
+        let mut updated: Vec<usize> = vec![];
+        for (idx, hostname) in missing {
+            entries[idx].add_alias(hostname);
+            if !updated.contains(&idx) {
+                updated.push(idx);
+                mods.updated_entries += 1;
+            }
+        }
+        Ok(mods)
+    }
+
+    /// Add an alias to the hostname, it is not limited to subdomains. The alias
+    /// can not already point to another IP address of the same family.
+    pub fn alias(
         &mut self,
         hostname: String,
         alias: String,
     ) -> Result<Modifications, ApplicationError> {
         let mut mods = Modifications::new();
 
+        let target = self
+            .entries
+            .iter()
+            .flatten()
+            .find(|e| e.name.is_some() && e.has_name(&hostname))
+            .and_then(|e| e.ip.map(|ip| (ip, e.zone.clone())));
+        if let Some((ip, zone)) = target {
+            for e in self.entries.iter().flatten() {
+                if e.has_name(&alias) && e.has_address(&ip, zone.as_deref()) {
+                    // already an alias, nothing to do
+                    return Ok(mods);
+                } else if e.has_name(&alias) && e.is_family(Family::of(&ip)) {
+                    return Err(ApplicationError::HostnameAlreadyInUse(format!("{}", e)));
+                }
+            }
+        }
+
         for item in self.entries.iter_mut().flatten() {
             let i = item;
 
@@ -285,19 +1264,927 @@ impl HostFile {
         }
         Err(ApplicationError::HostnameDoesNotExist(hostname))
     }
+
+    /// Make the hostname the canonical name of every line it is an alias on,
+    /// the name resolvers return for a reverse lookup of the IP address
+    pub fn canonical(&mut self, hostname: String) -> Result<Modifications, ApplicationError> {
+        let mut mods = Modifications::new();
+        let mut found = false;
+        for entry in self.entries.iter_mut().flatten() {
+            if entry.ip.is_none() || !entry.has_name(&hostname) {
+                continue;
+            }
+            found = true;
+            if entry.make_canonical(&hostname) {
+                mods.updated_entries += 1;
+            }
+        }
+        if !found {
+            return Err(ApplicationError::HostnameDoesNotExist(hostname));
+        }
+        Ok(mods)
+    }
+
+    /// Rename a hostname on every line it is on, keeping its position: the
+    /// canonical name stays the canonical name and an alias stays an alias.
+    pub fn rename(&mut self, old: String, new: String) -> Result<Modifications, ApplicationError> {
+        let mut mods = Modifications::new();
+
+        if !is_valid_hostname(&new) {
+            return Err(ApplicationError::InvalidHostname(new));
+        }
+        if let Some(e) = self.entries.iter().flatten().find(|e| e.has_name(&new)) {
+            return Err(ApplicationError::HostnameAlreadyInUse(format!("{}", e)));
+        }
+
+        for entry in self.entries.iter_mut().flatten() {
+            if entry.ip.is_some() && entry.rename(&old, &new) {
+                mods.updated_entries += 1;
+            }
+        }
+        if mods.updated_entries == 0 {
+            return Err(ApplicationError::HostnameDoesNotExist(old));
+        }
+        Ok(mods)
+    }
+
+    /// Move a hostname to another IP address of the same family. The line it
+    /// was on keeps its comment, the shortest alias becomes the canonical name
+    /// when it was the canonical name. On the line of the IP address it
+    /// becomes an alias, or the canonical name when it is the parent domain of
+    /// that name. Without a line for the IP address the hostname gets a new
+    /// line below the one it was on, or that line when it was its only name.
+    pub fn move_hostname(
+        &mut self,
+        hostname: String,
+        ip: String,
+    ) -> Result<Modifications, ApplicationError> {
+        let mut mods = Modifications::new();
+        let (ip, zone) = match parse_address(&ip) {
+            Some(address) => address,
+            None => return Err(ApplicationError::IpAddressConversion()),
+        };
+
+        let entries = self.entries.get_or_insert_with(Vec::new);
+        let idx = match entries
+            .iter()
+            .position(|e| e.has_name(&hostname) && e.is_family(Family::of(&ip)))
+        {
+            Some(idx) => idx,
+            None if entries
+                .iter()
+                .any(|e| e.ip.is_some() && e.has_name(&hostname)) =>
+            {
+                return Err(ApplicationError::FamilyMismatch(format!(
+                    "{} has no IPv{} address to move from",
+                    hostname,
+                    if ip.is_ipv4() { 4 } else { 6 }
+                )))
+            }
+            None => return Err(ApplicationError::HostnameDoesNotExist(hostname)),
+        };
+        if entries[idx].has_address(&ip, zone.as_deref()) {
+            return Ok(mods);
+        }
+
+        if entries[idx].names().len() == 1 {
+            if !entries.iter().any(|e| e.has_address(&ip, zone.as_deref())) {
+                // it is the only name, the line moves along
+                entries[idx].ip = Some(ip);
+                entries[idx].zone = zone;
+                mods.updated_entries += 1;
+                return Ok(mods);
+            }
+            match entries[idx].comment.take() {
+                Some(comment) => {
+                    entries[idx] = HostEntry {
+                        comment: Some(comment),
+                        ..HostEntry::empty()
+                    };
+                    mods.updated_entries += 1;
+                }
+                None => {
+                    entries.remove(idx);
+                    mods.removed_entries += 1;
+                }
+            }
+        } else {
+            let (_, rest) = entries[idx].remove_hostname(&hostname);
+            entries[idx] = rest;
+            mods.updated_entries += 1;
+        }
+
+        let target = entries
+            .iter_mut()
+            .find(|e| e.has_address(&ip, zone.as_deref()));
+        match target {
+            Some(t) if self.flavor == Flavor::Unix => {
+                if t.can_hostname_resolve_domain(&hostname) {
+                    t.switch_name_with_alias(&hostname);
+                } else {
+                    t.add_alias(&hostname);
+                }
+                mods.updated_entries += 1;
+            }
+            Some(_) => mods.merge(self.insert_name(hostname, ip, zone)),
+            None => {
+                entries.insert(
+                    idx + 1,
+                    HostEntry {
+                        ip: Some(ip),
+                        zone,
+                        name: Some(hostname),
+                        aliasses: None,
+                        comment: None,
+                    },
+                );
+                mods.added_entries += 1;
+            }
+        }
+        Ok(mods)
+    }
+}
+
+/// The absolute path of `path` as a file name, `/etc/hosts` is `%2Fetc%2Fhosts`
+fn path_name(path: &Path) -> String {
+    let absolute = fs::canonicalize(path)
+        .or_else(|_| std::env::current_dir().map(|dir| dir.join(path)))
+        .unwrap_or_else(|_| path.to_path_buf());
+    absolute
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace('/', "%2F")
+}
+
+/// `bytes` without the ASCII whitespace at the start and end
+fn trim_ascii(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |p| p + 1);
+    &bytes[start..end]
+}
+
+/// The columns of `token` in `line`, when it is a whole word on the line
+fn token_columns(line: &str, token: &str) -> Option<std::ops::Range<usize>> {
+    line.match_indices(token)
+        .find(|(i, _)| {
+            let before = line[..*i].chars().last();
+            let after = line[i + token.len()..].chars().next();
+            before.into_iter().all(char::is_whitespace)
+                && after.into_iter().all(|c| c.is_whitespace() || c == '#')
+        })
+        .map(|(i, _)| {
+            let start = line[..i].chars().count();
+            start..start + token.chars().count()
+        })
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::HostFile;
+    use crate::format::{Flavor, LineEnding};
+    use crate::hostentry::Family;
+    use crate::hostfile::STDIO;
+    use crate::utils::{Issue, Modifications, Severity};
+    use crate::{ApplicationError, HostEntry, HostFile};
     use std::net::IpAddr;
+    use std::path::PathBuf;
+
+    fn hostfile(lines: &[&str]) -> HostFile {
+        let mut hf = HostFile::new("/tmp/test");
+        hf.entries = Some(lines.iter().map(|l| l.parse().unwrap()).collect());
+        hf
+    }
 
     #[test]
+    fn test_section() {
+        let mut hf = hostfile(&["127.0.0.1 localhost", "", "# Active", "10.10.10.1 a.htb"]);
+        hf.section = Some(String::from("Active"));
+        hf.add(String::from("b.htb"), Some(String::from("10.10.10.2")))
+            .expect("Adding host");
+        hf.entries
+            .as_mut()
+            .unwrap()
+            .push("# Retired".parse().unwrap());
+        hf.add(String::from("c.htb"), Some(String::from("10.10.10.3")))
+            .expect("Adding host");
+        assert!(hf.entries.as_ref().unwrap()[5].has_name("c.htb"));
+        assert_eq!(
+            hf.entries.as_ref().unwrap()[6].comment.as_deref(),
+            Some("Retired")
+        );
+
+        hf.section = Some(String::from("Lab"));
+        hf.add(String::from("lab.local"), Some(String::from("10.0.0.1")))
+            .expect("Adding host");
+        let entries = hf.entries.as_ref().unwrap();
+        assert_eq!(entries[7], HostEntry::empty());
+        assert_eq!(entries[8].comment.as_deref(), Some("Lab"));
+        assert!(entries[9].has_name("lab.local"));
+    }
+
+    #[test]
+    fn test_backup_path() {
+        let mut hf = HostFile::new("/etc/hosts");
+        assert_eq!(hf.backup_path(0), PathBuf::from("/etc/hosts.bak"));
+        assert_eq!(hf.backup_path(2), PathBuf::from("/etc/hosts.bak.2"));
+
+        let mut other = HostFile::new("/nonexistent/hed/hosts");
+        other.backup_dir = Some(PathBuf::from("/var/backups/hed"));
+        assert_eq!(
+            other.backup_path(1),
+            PathBuf::from("/var/backups/hed/%2Fnonexistent%2Fhed%2Fhosts.bak.1")
+        );
+        hf.filename = String::from("hosts");
+        hf.backup_dir = other.backup_dir.clone();
+        assert_ne!(hf.backup_path(0), other.backup_path(0));
+        assert_eq!(
+            hf.backup_path(0).parent(),
+            Some(std::path::Path::new("/var/backups/hed"))
+        );
+
+        assert_eq!(
+            HostFile::new("hosts").backup_path(0),
+            PathBuf::from("hosts.bak")
+        );
+    }
+
+    #[test]
+    fn test_backup_rotation() {
+        let dir = std::env::temp_dir().join(format!("hed-backup-{}", std::process::id()));
+        let mut hf = HostFile::new(dir.join("hosts").display().to_string());
+        hf.backup_dir = Some(dir.join("backups"));
+        hf.backup_retention = 2;
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for content in ["one", "two", "three"] {
+            std::fs::write(&hf.filename, content).unwrap();
+            hf.backup().expect("Should backup");
+        }
+        assert_eq!(std::fs::read_to_string(hf.backup_path(0)).unwrap(), "three");
+        assert_eq!(std::fs::read_to_string(hf.backup_path(1)).unwrap(), "two");
+        assert!(!hf.backup_path(2).exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_from_and_write_to() {
+        let content = "# comment\n127.0.0.1\tlocalhost\n\n10.10.10.10\tbox.htb\tdemo.htb\n";
+        let mut hf = HostFile::new(STDIO);
+        assert!(hf.is_stdio());
+        hf.parse_from(content.as_bytes()).expect("Should parse");
+        assert_eq!(hf.entries.as_ref().unwrap().len(), 4);
+
+        let mut out = vec![];
+        hf.write_to(&mut out).expect("Should write");
+        assert_eq!(String::from_utf8(out).unwrap(), content);
+    }
+
+    #[test]
+    fn test_shadowed() {
+        let hf = hostfile(&[
+            "10.10.10.10 box.htb www.box.htb",
+            "fd00::10 box.htb",
+            "10.10.10.11 www.box.htb",
+            "10.10.10.10 box.htb",
+        ]);
+        let shadowed = hf.shadowed();
+        assert_eq!(shadowed.len(), 2);
+        assert_eq!(shadowed[0].line, 3);
+        assert_eq!(shadowed[0].name, "www.box.htb");
+        assert_eq!(shadowed[0].by_line, 1);
+        assert_eq!(shadowed[1].line, 4);
+        assert_eq!(shadowed[1].ip, shadowed[1].by_ip);
+    }
+
+    #[test]
+    fn test_add_first() {
+        let mut hf = hostfile(&[
+            "127.0.0.1 localhost",
+            "10.10.10.10 box.htb",
+            "fd00::1 box.htb",
+        ]);
+        let mods = hf
+            .add_first(String::from("box.htb"), String::from("10.10.10.20"))
+            .expect("Should add");
+        assert_eq!(mods.added_entries, 1);
+        assert!(hf.entries.as_ref().unwrap()[1].has_ip(&"10.10.10.20".parse().unwrap()));
+        assert_eq!(hf.shadowed().len(), 1);
+        assert_eq!(hf.shadowed()[0].line, 3);
+
+        // already the first mapping
+        let mods = hf
+            .add_first(String::from("box.htb"), String::from("10.10.10.20"))
+            .expect("Should add");
+        assert_eq!(mods, Modifications::new());
+
+        // no mapping yet, a regular add
+        hf.add_first(String::from("new.htb"), String::from("10.10.10.30"))
+            .expect("Should add");
+        assert!(hf.entries.as_ref().unwrap()[4].has_name("new.htb"));
+    }
+
+    #[test]
+    fn test_alias_overlap() {
+        let mut hf = hostfile(&[
+            "10.10.10.10 box.htb",
+            "10.10.10.11 other.htb",
+            "fd00::1 v6.htb",
+        ]);
+        assert!(hf
+            .alias(String::from("box.htb"), String::from("other.htb"))
+            .is_err());
+        assert_eq!(
+            hf.alias(String::from("other.htb"), String::from("other.htb"))
+                .unwrap(),
+            Modifications::new()
+        );
+        hf.alias(String::from("v6.htb"), String::from("box.htb"))
+            .expect("Should alias across address families");
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from(
+            "# lab\n10.10.10.10\tbox.htb\n10.0.0.300 x.htb\n10.10.10.11 www.htb box.htb\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let d = hf.diagnostics();
+        assert_eq!(d.len(), 2);
+        assert_eq!(d[0].line, 3);
+        assert_eq!(d[0].columns, 0..10);
+        assert_eq!(d[0].source, "10.0.0.300 x.htb");
+        assert_eq!(d[1].line, 4);
+        assert_eq!(d[1].columns, 20..27);
+
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from(&b"127.0.0.1 localhost\n# caf\xe9\n"[..])
+            .unwrap();
+        let d = hf.diagnostics();
+        assert_eq!(d.len(), 2);
+        let d = &d[1..];
+        assert_eq!(d[0].line, 2);
+        assert_eq!(d[0].severity, Severity::Warning);
+        assert_eq!(d[0].source, "# caf\u{FFFD}");
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let content = &b"127.0.0.1 localhost\r\n# caf\xe9 \xff\r\n10.0.0.1 box.htb"[..];
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from(content).unwrap();
+        assert_eq!(hf.non_utf8_lines(), vec![2]);
+        let mut out = vec![];
+        hf.write_to(&mut out).unwrap();
+        assert_eq!(out, content);
+
+        hf.line_ending = Some(LineEnding::Lf);
+        hf.final_newline = Some(true);
+        let mut out = vec![];
+        hf.write_to(&mut out).unwrap();
+        assert_eq!(
+            out,
+            b"127.0.0.1 localhost\n# caf\xe9 \xff\n10.0.0.1 box.htb\n"
+        );
+
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from("".as_bytes()).unwrap();
+        hf.add(String::from("box.htb"), Some(String::from("10.0.0.1")))
+            .unwrap();
+        let mut out = vec![];
+        hf.write_to(&mut out).unwrap();
+        assert_eq!(out, b"10.0.0.1\tbox.htb\n");
+
+        // the bytes stay with their line, a changed line keeps its comment
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from(&b"10.0.0.1\tbox.htb\n# caf\xe9\n10.0.0.2\tx.htb # caf\xe9 \xff\n"[..])
+            .unwrap();
+        hf.delete(String::from("box.htb"), None).unwrap();
+        hf.alias(String::from("x.htb"), String::from("y.htb"))
+            .unwrap();
+        let mut out = vec![];
+        hf.write_to(&mut out).unwrap();
+        assert_eq!(out, b"# caf\xe9\n10.0.0.2\tx.htb\ty.htb\t# caf\xe9 \xff\n");
+
+        // comments are written as they were read
+        let content = "#\n   # note\n#\tfoo\n#foo\n10.0.0.1  box.htb\n";
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from(content.as_bytes()).unwrap();
+        assert!(hf.entries.as_ref().unwrap()[..4]
+            .iter()
+            .all(|e| e.comment.is_some()));
+        hf.add(String::from("new.htb"), Some(String::from("10.0.0.2")))
+            .unwrap();
+        let mut out = vec![];
+        hf.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}10.0.0.2\tnew.htb\n", content)
+        );
+
+        // valid characters of private use plane 16 are not taken for bytes
+        let content = "# \u{10FF41}\u{10FFFF}\n10.0.0.1\tbox.htb\n";
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from(content.as_bytes()).unwrap();
+        assert!(hf.non_utf8_lines().is_empty());
+        let mut out = vec![];
+        hf.write_to(&mut out).unwrap();
+        assert_eq!(out, content.as_bytes());
+    }
+
+    #[test]
+    fn test_windows() {
+        // the stock hosts file of Windows is written back as it was
+        let stock = &include_bytes!("../samples/windows-default.txt")[..];
+        let mut hf = HostFile::new("hosts");
+        hf.flavor = Flavor::Windows;
+        hf.parse_from(stock).unwrap();
+        assert!(hf.validate().is_empty());
+        let mut out = vec![];
+        hf.write_to(&mut out).unwrap();
+        assert_eq!(out, stock);
+
+        let hosts = &b"10.10.10.10 box.htb\r\n10.10.10.11 other.htb\r\n"[..];
+        hf.parse_from(&[stock, hosts].concat()[..]).unwrap();
+        hf.add(String::from("www.box.htb"), None).unwrap();
+        hf.add(
+            String::from("api.box.htb"),
+            Some(String::from("10.10.10.10")),
+        )
+        .unwrap();
+        hf.add(String::from("new.htb"), Some(String::from("10.10.10.12")))
+            .unwrap();
+        assert!(hf
+            .add(String::from("box.htb"), Some(String::from("10.10.10.11")))
+            .is_err());
+        assert!(hf.validate().is_empty());
+
+        let mut out = vec![];
+        hf.write_to(&mut out).unwrap();
+        assert_eq!(
+            out,
+            [
+                stock,
+                b"10.10.10.10 box.htb\r\n\
+                  10.10.10.10\twww.box.htb\r\n\
+                  10.10.10.10\tapi.box.htb\r\n\
+                  10.10.10.11 other.htb\r\n\
+                  10.10.10.12\tnew.htb\r\n",
+            ]
+            .concat()
+        );
+
+        let mut hf = hostfile(&["127.0.0.1 localhost", "10.10.10.10 a b"]);
+        hf.flavor = Flavor::Windows;
+        let issues = hf.validate();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].text.as_deref(), Some("localhost"));
+        assert_eq!(issues[1].line, 2);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut hf = HostFile::new("/tmp/test");
+        hf.max_names = Some(2);
+        hf.parse_from(
+            "10.10.10.10 box.htb a.box.htb # lab\n\
+             10.10.10.10 b.box.htb c.box.htb\n\
+             10.10.10.10 d.box.htb\n\
+             10.10.10.300 x.htb\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(hf.entries.as_ref().unwrap().len(), 2);
+        assert_eq!(hf.lookup("d.box.htb")[0].0, 1);
+        let issues = hf.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 4);
+        assert_eq!(hf.diagnostics()[0].source, "10.10.10.300 x.htb");
+        hf.delete(String::from("x.htb"), None).unwrap();
+        assert!(hf
+            .add(String::from("c.box.htb"), Some(String::from("10.10.10.11")))
+            .is_err());
+        hf.add(String::from("e.box.htb"), None).unwrap();
+        hf.replace(
+            String::from("box.htb"),
+            String::from("10.10.10.12"),
+            None,
+            false,
+        )
+        .unwrap();
+
+        let mut out = vec![];
+        hf.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "10.10.10.12\tbox.htb\ta.box.htb\t# lab\n\
+             10.10.10.12\tb.box.htb\tc.box.htb\n\
+             10.10.10.12\td.box.htb\te.box.htb\n"
+        );
+
+        hf.max_names = None;
+        hf.max_line_length = Some(40);
+        let issues = hf.validate();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("3 lines"));
+    }
+
+    #[test]
+    fn test_dual_stack() {
+        let mut hf = hostfile(&["10.0.0.5 box.htb", "fd00::6 other.htb"]);
+        let mods = hf
+            .add_dual_stack(
+                String::from("box.htb"),
+                String::from("10.0.0.5"),
+                String::from("fd00::5"),
+            )
+            .unwrap();
+        assert_eq!(mods.added_entries, 1);
+        assert_eq!(hf.lookup("box.htb").len(), 2);
+        assert!(hf
+            .add_dual_stack(
+                String::from("box.htb"),
+                String::from("fd00::7"),
+                String::from("10.0.0.5"),
+            )
+            .is_err());
+        assert!(matches!(
+            hf.add_dual_stack(
+                String::from("new.htb"),
+                String::from("10.0.0.7"),
+                String::from("10.0.0.8"),
+            ),
+            Err(ApplicationError::FamilyMismatch(_))
+        ));
+        let mods = hf
+            .add_dual_stack(
+                String::from("www.other.htb"),
+                String::from("fd00::6"),
+                String::from("10.0.0.6"),
+            )
+            .unwrap();
+        assert_eq!((mods.added_entries, mods.updated_entries), (1, 1));
+
+        hf.add(
+            String::from("router.lan"),
+            Some(String::from("fe80::1%eth0")),
+        )
+        .unwrap();
+        assert_eq!(
+            hf.entries.as_ref().unwrap().last().unwrap().to_string(),
+            "fe80::1%eth0\trouter.lan"
+        );
+        let mods = hf
+            .add(
+                String::from("printer.lan"),
+                Some(String::from("fe80::1%wlan0")),
+            )
+            .unwrap();
+        assert_eq!(mods.added_entries, 1);
+        let mods = hf.delete(String::from("fe80::1%eth0"), None).unwrap();
+        assert_eq!(mods.removed_entries, 1);
+        assert!(hf.lookup("router.lan").is_empty());
+        assert_eq!(hf.lookup("printer.lan").len(), 1);
+    }
+
+    #[test]
+    fn test_families() {
+        let mut hf = hostfile(&["fd00::5 box.htb www.box.htb", "10.0.0.6 other.htb"]);
+        hf.add(String::from("box.htb"), Some(String::from("10.0.0.5")))
+            .expect("Should add the IPv4 address");
+        assert!(hf
+            .add(String::from("box.htb"), Some(String::from("10.0.0.7")))
+            .is_err());
+        assert!(hf
+            .add(String::from("other.htb"), Some(String::from("fd00::6")))
+            .is_ok());
+
+        hf.replace(
+            String::from("box.htb"),
+            String::from("10.0.0.8"),
+            None,
+            false,
+        )
+        .unwrap();
+        let found = hf.lookup("box.htb");
+        assert_eq!(found[0].1.ip, Some("fd00::5".parse().unwrap()));
+        assert_eq!(found[1].1.ip, Some("10.0.0.8".parse().unwrap()));
+
+        hf.delete(String::from("www.box.htb"), Some(Family::V4))
+            .unwrap();
+        assert!(hf.entries.as_ref().unwrap()[0].has_name("www.box.htb"));
+        hf.delete(String::from("box.htb"), Some(Family::V6))
+            .unwrap();
+        let found = hf.lookup("box.htb");
+        assert_eq!(found.len(), 1);
+        assert!(found[0].1.is_family(Family::V4));
+        assert!(matches!(
+            hf.delete(String::from("10.0.0.8"), Some(Family::V6)),
+            Err(ApplicationError::UnknownOption(_))
+        ));
+        assert_eq!(hf.lookup("box.htb").len(), 1);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut hf = hostfile(&["10.0.0.5 box.htb www.box.htb api.box.htb # lab"]);
+        assert!(matches!(
+            hf.replace(
+                String::from("missing.htb"),
+                String::from("10.0.0.6"),
+                None,
+                false
+            ),
+            Err(ApplicationError::HostnameDoesNotExist(_))
+        ));
+
+        let mods = hf
+            .replace(
+                String::from("www.box.htb"),
+                String::from("10.0.0.6"),
+                None,
+                true,
+            )
+            .unwrap();
+        assert_eq!((mods.updated_entries, mods.added_entries), (1, 1));
+        let entries = hf.entries.as_ref().unwrap();
+        assert_eq!(
+            entries[0].to_string(),
+            "10.0.0.5\tbox.htb\tapi.box.htb\t# lab"
+        );
+        assert_eq!(entries[1].to_string(), "10.0.0.6\twww.box.htb");
+
+        hf.replace(
+            String::from("api.box.htb"),
+            String::from("10.0.0.7"),
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            hf.entries.as_ref().unwrap()[0].to_string(),
+            "10.0.0.7\tbox.htb\tapi.box.htb\t# lab"
+        );
+
+        // the only address of the hostname is of the other family
+        hf.replace(
+            String::from("box.htb"),
+            String::from("fd00::7"),
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(hf.lookup("box.htb").len(), 1);
+        assert!(hf.lookup("box.htb")[0].1.is_family(Family::V6));
+        assert!(hf
+            .replace(
+                String::from("box.htb"),
+                String::from("10.0.0.8"),
+                Some(Family::V4),
+                false,
+            )
+            .is_err());
+
+        // the other family of a dual-stack host is not replaced by a second line
+        let mut hf = hostfile(&["10.0.0.5 box.htb", "fd00::5 box.htb"]);
+        assert!(matches!(
+            hf.replace(
+                String::from("box.htb"),
+                String::from("fd00::6"),
+                Some(Family::V4),
+                false,
+            ),
+            Err(ApplicationError::HostnameAlreadyInUse(_))
+        ));
+        assert_eq!(
+            hf.entries.as_ref().unwrap()[0].ip,
+            Some("10.0.0.5".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_rename_and_move() {
+        let mut hf = hostfile(&[
+            "10.0.0.5 box.htb www.box.htb # lab",
+            "fd00::5 box.htb",
+            "10.0.0.6 other.htb",
+        ]);
+        hf.rename(String::from("box.htb"), String::from("new.htb"))
+            .unwrap();
+        assert_eq!(hf.lookup("new.htb").len(), 2);
+        assert!(hf
+            .rename(String::from("missing.htb"), String::from("x.htb"))
+            .is_err());
+        assert!(hf
+            .rename(String::from("new.htb"), String::from("other.htb"))
+            .is_err());
+
+        hf.move_hostname(String::from("new.htb"), String::from("10.0.0.6"))
+            .unwrap();
+        hf.move_hostname(String::from("www.box.htb"), String::from("10.0.0.7"))
+            .unwrap();
+        let lines: Vec<String> = hf
+            .entries
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "10.0.0.7\twww.box.htb\t# lab",
+                "fd00::5\tnew.htb",
+                "10.0.0.6\tother.htb\tnew.htb",
+            ]
+        );
+        assert!(matches!(
+            hf.move_hostname(String::from("other.htb"), String::from("fd00::6")),
+            Err(ApplicationError::FamilyMismatch(_))
+        ));
+        assert!(matches!(
+            hf.move_hostname(String::from("missing.htb"), String::from("10.0.0.6")),
+            Err(ApplicationError::HostnameDoesNotExist(_))
+        ));
+
+        hf.move_hostname(String::from("www.box.htb"), String::from("10.0.0.6"))
+            .unwrap();
+        assert_eq!(hf.entries.as_ref().unwrap()[0].to_string(), "# lab");
+    }
+
+    #[test]
+    fn test_canonical() {
+        let mut hf = hostfile(&[
+            "127.0.0.1 localhost localhost.localdomain",
+            "10.0.0.5 box box.htb",
+            "fd00::5 box.htb box",
+        ]);
+        let issues = hf.validate();
+        let fqdn: Vec<&Issue> = issues.iter().filter(|i| i.line == 2).collect();
+        assert_eq!(fqdn.len(), 1);
+        assert_eq!(fqdn[0].text.as_deref(), Some("box"));
+
+        let mods = hf.canonical(String::from("box.htb")).unwrap();
+        assert_eq!(mods.updated_entries, 1);
+        assert_eq!(
+            hf.entries.as_ref().unwrap()[1].to_string(),
+            "10.0.0.5\tbox.htb\tbox"
+        );
+        assert!(hf.canonical(String::from("missing.htb")).is_err());
+    }
+
+    #[test]
+    fn test_who() {
+        let hf = hostfile(&[
+            "10.10.10.5 box.htb www.box.htb",
+            "10.10.10.6 other.htb",
+            "fd00::5 box.htb",
+            "10.10.10.5 api.box.htb www.box.htb",
+            "10.10.11.5 far.htb",
+        ]);
+        let found = hf.who(&"10.10.10.5".parse().unwrap());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].lines, vec![1, 4]);
+        assert_eq!(
+            found[0].names,
+            vec!["box.htb", "www.box.htb", "api.box.htb"]
+        );
+        assert_eq!(found[0].answer, vec!["box.htb", "www.box.htb"]);
+
+        let found = hf.who(&"10.10.10.0/24".parse().unwrap());
+        let ips: Vec<String> = found.iter().map(|r| r.ip.to_string()).collect();
+        assert_eq!(ips, vec!["10.10.10.5", "10.10.10.6"]);
+        assert!(hf.who(&"10.0.0.1".parse().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_add_existing() {
+        let mut hf = hostfile(&["10.10.10.10 box.htb www.box.htb"]);
+        let mods = hf
+            .add(
+                String::from("www.box.htb"),
+                Some(String::from("10.10.10.10")),
+            )
+            .expect("Should be a no-op");
+        assert_eq!(mods, Modifications::new());
+        assert!(hf
+            .add(
+                String::from("www.box.htb"),
+                Some(String::from("10.10.10.11"))
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_add_subdomains() {
+        let mut hf = hostfile(&["10.10.10.10 box.htb", "10.10.10.11 dev.other.htb"]);
+        let mods = hf
+            .add_subdomains("box.htb", &["api", "admin.box.htb", "", "api"])
+            .expect("Should add");
+        assert_eq!(mods.updated_entries, 1);
+        assert_eq!(
+            format!("{}", hf.entries.as_ref().unwrap()[0]),
+            "10.10.10.10\tbox.htb\tapi.box.htb admin.box.htb"
+        );
+
+        // adding them again changes nothing
+        let mods = hf.add_subdomains("box.htb", &["api"]).expect("Should add");
+        assert_eq!(mods, Modifications::new());
+
+        assert!(hf.add_subdomains("other.htb", &["api"]).is_err());
+        assert!(hf.add_subdomains("box.htb", &["bad_name"]).is_err());
+        hf.alias(String::from("dev.other.htb"), String::from("dev.box.htb"))
+            .expect("Should alias");
+        assert!(hf.add_subdomains("box.htb", &["dev"]).is_err());
+
+        let mut hf = hostfile(&[
+            "10.10.10.10 box.htb",
+            "fd00::10 box.htb",
+            "fd00::11 api.box.htb",
+            "10.10.10.10 www.box.htb",
+        ]);
+        assert!(hf.add_subdomains("box.htb", &["api"]).is_err());
+        let mods = hf
+            .add_subdomains("box.htb", &["www", "admin"])
+            .expect("Should add");
+        assert_eq!(mods.updated_entries, 2);
+        let entries = hf.entries.as_ref().unwrap();
+        assert_eq!(
+            entries[0].to_string(),
+            "10.10.10.10\tbox.htb\tadmin.box.htb"
+        );
+        assert_eq!(
+            entries[1].to_string(),
+            "fd00::10\tbox.htb\twww.box.htb admin.box.htb"
+        );
+
+        let mut hf = hostfile(&["10.10.10.10 box.htb", "fd00::11 api.box.htb"]);
+        hf.add_subdomains("box.htb", &["api"]).expect("Should add");
+        assert_eq!(hf.lookup("api.box.htb").len(), 2);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let hf = hostfile(&[
+            "# box.htb",
+            "10.10.10.10 box.htb",
+            "::1 localhost",
+            "10.10.10.11 www.box.htb box.htb",
+        ]);
+        let found = hf.lookup("box.htb");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0, 2);
+        assert_eq!(found[1].0, 4);
+        assert!(hf.lookup("demo.htb").is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let hf = hostfile(&[
+            "# comment",
+            "127.0.0.1 localhost",
+            "::1 localhost",
+            "",
+            "10.10.10.10 box.htb",
+        ]);
+        assert!(hf.validate().is_empty());
+
+        let hf = hostfile(&[
+            "127.0.0.1 localhost",
+            "10.10.10.300 box.htb",
+            "10.10.10.10 bad_name.htb",
+            "10.10.10.11 localhost",
+            "127.0.0.1 localhost",
+        ]);
+        let issues = hf.validate();
+        assert_eq!(issues.len(), 5);
+        assert_eq!(issues[0].line, 1);
+        assert!(issues[0].message.contains("::1 localhost"));
+        let issues = &issues[1..];
+        assert_eq!(issues[0].line, 2);
+        assert_eq!(
+            issues[1],
+            Issue::error(3, String::from("malformed hostname `bad_name.htb`"))
+                .with_text("bad_name.htb")
+        );
+        assert_eq!(issues[2].line, 4);
+        assert_eq!(issues[2].severity, Severity::Error);
+        assert_eq!(issues[3].line, 5);
+        assert_eq!(issues[3].severity, Severity::Warning);
+    }
+
+    #[test]
+    // the alias assertion predates clippy on this test
+    #[allow(clippy::single_match, clippy::bool_assert_comparison)]
     fn test_functions() {
-        let mut hf = HostFile {
-            filename: "/tmp/test".to_string(),
-            entries: None,
-        };
+        let mut hf = HostFile::new("/tmp/test");
 
         assert!(hf.entries.is_none());
 
@@ -312,7 +2199,8 @@ mod tests {
         assert!(hf.entries.is_some());
 
         // remove it by ip
-        hf.delete(String::from("127.0.0.1")).expect("Should delete");
+        hf.delete(String::from("127.0.0.1"), None)
+            .expect("Should delete");
         println!("{:?}", hf);
         assert_eq!(hf.entries.as_ref().unwrap().len(), 0);
 
@@ -324,7 +2212,7 @@ mod tests {
         assert!(hf.entries.is_some());
 
         // remove it by name
-        hf.delete(String::from("arjenwiersma.nl"))
+        hf.delete(String::from("arjenwiersma.nl"), None)
             .expect("Should delete");
         assert_eq!(hf.entries.as_ref().unwrap().len(), 0);
 
@@ -354,7 +2242,8 @@ mod tests {
             "me.arjenwiersma.nl"
         );
 
-        hf.delete(String::from("127.0.0.1")).expect("Should delete");
+        hf.delete(String::from("127.0.0.1"), None)
+            .expect("Should delete");
         assert_eq!(hf.entries.as_ref().unwrap().len(), 0);
 
         // parent first
@@ -393,7 +2282,9 @@ mod tests {
         // replace the ip address of the domain
         hf.replace(
             String::from("arjenwiersma.nl"),
-            Some(String::from("192.168.0.1")),
+            String::from("192.168.0.1"),
+            None,
+            false,
         )
         .expect("should replace");
         let e = hf.entries.clone();
@@ -0,0 +1,41 @@
+//! Manage hosts files, the library behind the `hed` command.
+//!
+//! A [`HostFile`] holds the lines of a hosts file as [`HostEntry`] values. It
+//! can be read from a path or any reader, modified with `add`, `replace`,
+//! `alias` and `delete`, and written back to the path (with a backup) or to
+//! any writer. The library never escalates privileges by itself, the caller
+//! needs to be allowed to write the file.
+//!
+//! A hostfile named `-` ([`hostfile::STDIO`]) is read from stdin and written
+//! to stdout.
+//!
+//! ```
+//! use hed::HostFile;
+//!
+//! let mut hf = HostFile::new("hosts");
+//! hf.parse_from("127.0.0.1\tlocalhost\n".as_bytes()).unwrap();
+//! hf.add(String::from("box.htb"), Some(String::from("10.10.10.5")))
+//!     .unwrap();
+//!
+//! let mut out = vec![];
+//! hf.write_to(&mut out).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(out).unwrap(),
+//!     "127.0.0.1\tlocalhost\n10.10.10.5\tbox.htb\n"
+//! );
+//! ```
+
+pub mod errors;
+pub mod format;
+pub mod hostentry;
+pub mod hostfile;
+pub mod theme;
+pub mod utils;
+pub mod view;
+
+pub use errors::ApplicationError;
+pub use hostentry::{Family, HostEntry};
+pub use hostfile::HostFile;
+pub use theme::Theme;
+pub use utils::{Diagnostic, Issue, Modifications, Reverse, Severity, Shadowed};
+pub use view::ShowOptions;
@@ -1,19 +1,26 @@
-use std::process::exit;
+use std::{
+    env, fs,
+    io::{self, Write},
+    process::exit,
+};
 
 use clap::Parser;
 pub(crate) use color_eyre::eyre::Result;
-use errors::ApplicationError;
-use termion::color;
-use utils::Modifications;
+use hed::{ApplicationError, HostFile, Modifications, Severity, ShowOptions, Theme};
 
-use crate::hostfile::HostFile;
-pub mod app;
-pub mod errors;
-pub mod hostentry;
-pub mod hostfile;
-pub mod utils;
+mod app;
+mod check;
+mod completions;
+mod config;
+mod editor;
+mod escalate;
+mod helper;
+mod man;
+mod who;
 
 use app::Commands;
+use config::Config;
+use escalate::Escalation;
 
 fn main() {
     if let Err(e) = color_eyre::install() {
@@ -23,133 +30,476 @@ fn main() {
 
     let matches = app::Cli::parse();
 
-    let mut hf = HostFile {
-        filename: matches.file,
-        entries: None,
+    // the helper runs with elevated privileges, keep it away from the rest
+    if let Commands::WriteHelper {} = matches.command {
+        if let Err(e) = helper::write_helper(matches.file.as_deref()) {
+            eprintln!("{}", e);
+            exit(exits::code(&e));
+        }
+        exit(exits::SUCCESS);
+    }
+
+    // completions and the man page do not need the configuration or the hostfile
+    if let Commands::Completions { shell } = matches.command {
+        if let Err(e) = completions::generate(shell, &mut io::stdout()) {
+            eprintln!("{}", e);
+            exit(exits::code(&e));
+        }
+        exit(exits::SUCCESS);
+    }
+    if let Commands::Man {} = matches.command {
+        if let Err(e) = man::render(&mut io::stdout()) {
+            eprintln!("{}", e);
+            exit(exits::IO_ERROR);
+        }
+        exit(exits::SUCCESS);
+    }
+
+    let config = match Config::load(matches.config.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(exits::code(&e));
+        }
+    };
+    let configured = match config.theme() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(exits::code(&e));
+        }
     };
+    let colored = |tty: bool| {
+        if matches.color.enabled(tty, env::var_os("NO_COLOR")) {
+            configured.clone()
+        } else {
+            Theme::plain()
+        }
+    };
+    let theme = colored(termion::is_tty(&io::stdout()));
+    let err_theme = colored(termion::is_tty(&io::stderr()));
+    let escalation = if matches.no_escalate {
+        Escalation::None
+    } else {
+        config.escalation()
+    };
+
+    let mut hf = config.hostfile(matches.file);
+    if let Some(align) = matches.align {
+        hf.align = align;
+    }
+    if let Some(flavor) = matches.flavor {
+        hf.flavor = flavor;
+    }
+    let hf_is_stdio = hf.is_stdio();
+
+    // the completion scripts pass on `--file -`, stdin is the terminal then
+    if hf_is_stdio && matches!(matches.command, Commands::CompleteHosts {}) {
+        let e = ApplicationError::HostFileUnreadable(String::from(
+            "the hostnames can not be completed from stdin",
+        ));
+        eprintln!("{}", e);
+        exit(exits::code(&e));
+    }
 
     if let Err(e) = hf.parse() {
-        eprintln!("Failed to parse the hostfile, this should not happen unless you are using --file to override the file.\nThe error message is: {}", e);
-        exit(exits::RUNTIME_ERROR);
+        eprintln!("Failed to parse the hostfile. {}", e);
+        exit(exits::code(&e));
+    }
+    // verify reports these itself
+    if !matches!(matches.command, Commands::Verify {}) {
+        for line in hf.non_utf8_lines() {
+            eprintln!(
+                "warning: line {} is not valid UTF-8, its bytes are kept as they are",
+                line
+            );
+        }
     }
 
+    // an add that changes nothing has its own exit code
+    let mut unchanged = false;
     let res: Result<Modifications, ApplicationError> = match matches.command {
-        Commands::Verify {} => verify(hf),
-        Commands::Show {} => hf.show(),
-        Commands::Add { hostname, ip } => match hf.add(hostname, ip) {
-            Ok(m) => {
-                let r = hf.write();
-                if r.is_err() {
-                    Err(r.err().unwrap())
+        Commands::Verify {} => verify(hf, config.strict(), &theme, &err_theme),
+        Commands::Show {
+            sort,
+            group_by,
+            only,
+        } => hf.show_with(
+            &mut io::stdout(),
+            &theme,
+            &ShowOptions {
+                sort,
+                group_by,
+                only,
+            },
+        ),
+        Commands::Check { hostname } => check::check(&hf, &hostname),
+        Commands::Who { address } => who::who(&hf, &address, &theme),
+        Commands::Edit {} => match editor::edit(&mut hf, config.strict()) {
+            Ok(m) if m == Modifications::new() => Ok(m),
+            Ok(m) => save(&hf, m, escalation),
+            Err(e) => {
+                eprintln!("Failed to process command: {}", e);
+                exit(exits::code(&e));
+            }
+        },
+        Commands::Add {
+            hostname,
+            ip,
+            other_ip,
+            dual_stack: _,
+            force_first,
+            subdomains,
+            exit_unchanged,
+        } => match add(
+            &mut hf,
+            hostname.clone(),
+            ip,
+            other_ip,
+            force_first,
+            &subdomains,
+        ) {
+            Ok(m) if m == Modifications::new() => {
+                if subdomains.is_empty() {
+                    eprintln!("Hostname already exists in the hostfile");
+                } else {
+                    eprintln!("The subdomains are already aliases of {}", hostname);
+                }
+                unchanged = exit_unchanged;
+                if hf.is_stdio() {
+                    save(&hf, m, escalation)
                 } else {
                     Ok(m)
                 }
             }
-
+            Ok(m) => save(&hf, m, escalation),
             Err(e) => {
                 eprintln!("Failed to process command: {}", e);
-                exit(exits::RUNTIME_ERROR);
+                exit(exits::code(&e));
             }
         },
-        Commands::Replace { hostname, ip } => match hf.replace(hostname, ip) {
-            Ok(m) => {
-                let r = hf.write();
-                if r.is_err() {
-                    Err(r.err().unwrap())
-                } else {
-                    Ok(m)
+        Commands::Expand { domain, wordlist } => {
+            match read_wordlist(&wordlist).and_then(|words| hf.add_subdomains(&domain, &words)) {
+                Ok(m) => save(&hf, m, escalation),
+                Err(e) => {
+                    eprintln!("Failed to process command: {}", e);
+                    exit(exits::code(&e));
                 }
             }
+        }
+        Commands::Replace {
+            hostname,
+            ip,
+            family,
+            only_this_name,
+        } => match hf.replace(hostname, ip.unwrap_or_default(), family, only_this_name) {
+            Ok(m) => save(&hf, m, escalation),
             Err(e) => {
                 eprintln!("Failed to process command: {}", e);
-                exit(exits::RUNTIME_ERROR);
+                exit(exits::code(&e));
             }
         },
         Commands::Alias { hostname, alias } => match hf.alias(hostname, alias) {
-            Ok(m) => {
-                let r = hf.write();
-                if r.is_err() {
-                    Err(r.err().unwrap())
-                } else {
-                    Ok(m)
-                }
+            Ok(m) => save(&hf, m, escalation),
+            Err(e) => {
+                eprintln!("Failed to process command: {}", e);
+                exit(exits::code(&e));
             }
+        },
+        Commands::Canonical { hostname } => match hf.canonical(hostname) {
+            Ok(m) => save(&hf, m, escalation),
             Err(e) => {
                 eprintln!("Failed to process command: {}", e);
-                exit(exits::RUNTIME_ERROR);
+                exit(exits::code(&e));
             }
         },
-        Commands::Delete { entry } => match hf.delete(entry) {
-            Ok(m) => {
-                let r = hf.write();
-                if r.is_err() {
-                    Err(r.err().unwrap())
-                } else {
-                    Ok(m)
-                }
+        Commands::Rename { old, new } => match hf.rename(old, new) {
+            Ok(m) => save(&hf, m, escalation),
+            Err(e) => {
+                eprintln!("Failed to process command: {}", e);
+                exit(exits::code(&e));
+            }
+        },
+        Commands::Move { hostname, ip } => match hf.move_hostname(hostname, ip) {
+            Ok(m) => save(&hf, m, escalation),
+            Err(e) => {
+                eprintln!("Failed to process command: {}", e);
+                exit(exits::code(&e));
             }
+        },
+        Commands::Delete { entry, family } => match hf.delete(entry, family) {
+            Ok(m) => save(&hf, m, escalation),
             Err(e) => {
                 eprintln!("Failed to process command: {}", e);
-                exit(exits::RUNTIME_ERROR);
+                exit(exits::code(&e));
             }
         },
+        Commands::CompleteHosts {} => completions::hosts(&hf, &mut io::stdout()),
+        // handled before the hostfile is parsed
+        Commands::WriteHelper {} | Commands::Completions { .. } | Commands::Man {} => {
+            Err(ApplicationError::FileABugReport())
+        }
     };
 
     match res {
         Ok(m) => {
-            print_status(m);
+            // the hostfile itself goes to stdout when filtering
+            if hf_is_stdio {
+                print_status(&mut io::stderr(), m, &err_theme);
+            } else {
+                print_status(&mut io::stdout(), m, &theme);
+            }
+            if unchanged {
+                exit(exits::UNCHANGED);
+            }
             exit(exits::SUCCESS);
         }
         Err(e) => {
             eprintln!("{}", e);
-            exit(exits::RUNTIME_ERROR);
+            exit(exits::code(&e));
         }
     };
 }
 
-fn print_status(mods: Modifications) {
+/// Add the hostname, and its subdomains as aliases when they are given
+fn add(
+    hf: &mut HostFile,
+    hostname: String,
+    ip: Option<String>,
+    other_ip: Option<String>,
+    force_first: bool,
+    subdomains: &[String],
+) -> Result<Modifications, ApplicationError> {
+    // clap only accepts the other IP address with --dual-stack
+    if let (Some(ip), Some(other)) = (ip.clone(), other_ip) {
+        return hf.add_dual_stack(hostname, ip, other);
+    }
+    if subdomains.is_empty() {
+        return match ip {
+            Some(ip) if force_first => hf.add_first(hostname, ip),
+            _ => hf.add(hostname, ip),
+        };
+    }
+
+    // without an IP address the domain has to be in the hostfile already
+    let mut mods = match ip {
+        Some(_) => hf.add(hostname.clone(), ip)?,
+        None => Modifications::new(),
+    };
+    mods.merge(hf.add_subdomains(&hostname, subdomains)?);
+    Ok(mods)
+}
+
+/// The words in a wordlist, one per line. Empty lines and `#` comments are
+/// skipped.
+fn read_wordlist(path: &str) -> Result<Vec<String>, ApplicationError> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| ApplicationError::FileNotParseable(format!("{}: {}", path, e)))?;
+    Ok(content
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Write the modified hostfile, with elevated privileges when needed
+fn save(
+    hf: &HostFile,
+    mods: Modifications,
+    escalation: Escalation,
+) -> Result<Modifications, ApplicationError> {
+    escalate::write(hf, escalation)?;
+    Ok(mods)
+}
+
+fn print_status<W: Write>(out: &mut W, mods: Modifications, theme: &Theme) {
     if mods.added_entries > 0 {
-        println!(
+        let _ = writeln!(
+            out,
             "Added {}{}{} entries",
-            color::Fg(color::Green),
-            mods.added_entries,
-            color::Fg(color::Reset)
-        )
+            theme.highlight, mods.added_entries, theme.reset
+        );
     }
     if mods.updated_entries > 0 {
-        println!(
+        let _ = writeln!(
+            out,
             "Updated {}{}{} entries",
-            color::Fg(color::Green),
-            mods.updated_entries,
-            color::Fg(color::Reset)
-        )
+            theme.highlight, mods.updated_entries, theme.reset
+        );
     }
     if mods.removed_entries > 0 {
-        println!(
+        let _ = writeln!(
+            out,
             "Removed {}{}{} entries",
-            color::Fg(color::Green),
-            mods.removed_entries,
-            color::Fg(color::Reset)
-        )
+            theme.highlight, mods.removed_entries, theme.reset
+        );
     }
 }
 
-/// Verify that the host file is parsable
-fn verify(hf: HostFile) -> Result<Modifications, ApplicationError> {
+/// Verify that the host file is parsable and report the problems found in it,
+/// when `strict` warnings count as problems as well.
+fn verify(
+    hf: HostFile,
+    strict: bool,
+    theme: &Theme,
+    err_theme: &Theme,
+) -> Result<Modifications, ApplicationError> {
+    let diagnostics = hf.diagnostics();
+    for d in &diagnostics {
+        d.color_print(&mut io::stderr(), err_theme)?;
+        eprintln!();
+    }
+    let problems = diagnostics
+        .iter()
+        .filter(|d| strict || d.severity == Severity::Error)
+        .count();
+    if problems > 0 {
+        return Err(ApplicationError::ValidationFailed(problems));
+    }
+
     println!(
         "Hostsfile is readable and contains {}{}{} entries.",
-        color::Fg(color::Green),
+        theme.highlight,
         hf.entries.unwrap_or_default().len(),
-        color::Fg(color::Reset),
+        theme.reset,
     );
     Ok(Modifications::new())
 }
 
 mod exits {
+    use hed::ApplicationError;
 
-    /// Exit code for when exa runs OK.
+    /// Exit code for when hed runs OK.
     pub const SUCCESS: i32 = 0;
 
-    /// Exit code for when there was at least one I/O error during execution.
+    /// Exit code for failures that have no category of their own.
     pub const RUNTIME_ERROR: i32 = 1;
+
+    /// Exit code clap uses for invalid command line arguments.
+    pub const USAGE_ERROR: i32 = 2;
+
+    /// Exit code for a hostname or IP address that is used by another entry.
+    pub const CONFLICT: i32 = 3;
+
+    /// Exit code for a hostname or domain that is not in the hostfile.
+    pub const NOT_FOUND: i32 = 4;
+
+    /// Exit code for an IP address, hostname or option that is not valid.
+    pub const INVALID_INPUT: i32 = 5;
+
+    /// Exit code for a hostfile that can not be written.
+    pub const PERMISSION: i32 = 6;
+
+    /// Exit code for failing to read or write a file or run a program.
+    pub const IO_ERROR: i32 = 7;
+
+    /// Exit code for a hostfile or configuration that is not valid.
+    pub const PARSE_ERROR: i32 = 8;
+
+    /// Exit code for a hostfile entry the system resolver does not return.
+    pub const MISMATCH: i32 = 9;
+
+    /// Exit code for an edit that was aborted.
+    pub const ABORTED: i32 = 10;
+
+    /// Exit code for an add of a mapping that is already in the hostfile,
+    /// with `add --exit-unchanged`.
+    pub const UNCHANGED: i32 = 11;
+
+    /// The exit codes with their meaning, for the man page.
+    pub const DOCUMENTED: &[(i32, &str)] = &[
+        (SUCCESS, "The command succeeded."),
+        (RUNTIME_ERROR, "The command failed for another reason."),
+        (USAGE_ERROR, "The command line arguments are not valid."),
+        (
+            CONFLICT,
+            "The hostname or IP address is already used by another entry.",
+        ),
+        (
+            NOT_FOUND,
+            "The hostname, its parent domain or the IP address is not in the hosts file.",
+        ),
+        (
+            INVALID_INPUT,
+            "The IP address or its family, the hostname or an option is not valid.",
+        ),
+        (PERMISSION, "The hosts file can not be written."),
+        (
+            IO_ERROR,
+            "A file could not be read or written or a program could not be run.",
+        ),
+        (
+            PARSE_ERROR,
+            "The hosts file or the configuration is not valid.",
+        ),
+        (
+            MISMATCH,
+            "The system resolver does not return the entry in the hosts file.",
+        ),
+        (
+            ABORTED,
+            "The edit was aborted, the hosts file was not changed.",
+        ),
+        (
+            UNCHANGED,
+            "With add --exit-unchanged: the mapping is already in the hosts file, nothing was changed.",
+        ),
+    ];
+
+    /// The exit code for `e`
+    pub fn code(e: &ApplicationError) -> i32 {
+        match e {
+            ApplicationError::IpAlreadyInUse(_) | ApplicationError::HostnameAlreadyInUse(_) => {
+                CONFLICT
+            }
+            ApplicationError::HostnameDoesNotExist(_)
+            | ApplicationError::NoParentDomain()
+            | ApplicationError::AddressNotFound(_) => NOT_FOUND,
+            ApplicationError::IpAddressConversion()
+            | ApplicationError::InvalidHostname(_)
+            | ApplicationError::NoHostnameGiven()
+            | ApplicationError::UnknownOption(_)
+            | ApplicationError::FamilyMismatch(_) => INVALID_INPUT,
+            ApplicationError::HostFileUnwritable(_) => PERMISSION,
+            ApplicationError::HostFileUnreadable(_)
+            | ApplicationError::BackupFileWriteFailed(_)
+            | ApplicationError::EditorFailed(_)
+            | ApplicationError::IOError(_) => IO_ERROR,
+            ApplicationError::FileNotParseable(_)
+            | ApplicationError::ConfigNotParseable(_)
+            | ApplicationError::ValidationFailed(_) => PARSE_ERROR,
+            ApplicationError::ResolutionMismatch(_) => MISMATCH,
+            ApplicationError::EditAborted() => ABORTED,
+            ApplicationError::FileABugReport() => RUNTIME_ERROR,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hed::ApplicationError;
+
+    use crate::exits;
+
+    #[test]
+    fn test_exit_codes() {
+        let mut codes: Vec<i32> = exits::DOCUMENTED.iter().map(|(c, _)| *c).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), exits::DOCUMENTED.len());
+
+        assert_eq!(
+            exits::code(&ApplicationError::HostnameAlreadyInUse(String::new())),
+            exits::CONFLICT
+        );
+        assert_eq!(
+            exits::code(&ApplicationError::HostFileUnwritable(String::new())),
+            exits::PERMISSION
+        );
+        assert_eq!(
+            exits::code(&ApplicationError::ValidationFailed(1)),
+            exits::PARSE_ERROR
+        );
+    }
 }
@@ -0,0 +1,236 @@
+use std::io::{self, Write};
+
+use clap::{Arg, Command, CommandFactory};
+use clap_mangen::{
+    roff::{bold, italic, roman, Roff},
+    Man,
+};
+
+use crate::app::Cli;
+use crate::exits;
+
+/// Example invocations with what they do
+const EXAMPLES: &[(&str, &str)] = &[
+    (
+        "hed show --group-by subnet --sort ip",
+        "List the hosts file grouped by network.",
+    ),
+    ("hed add box.htb 10.10.10.5", "Add a host."),
+    (
+        "hed add --dual-stack box.htb 10.10.10.5 fd00::5",
+        "Add a host for both IPv4 and IPv6.",
+    ),
+    (
+        "hed add --subdomains api,admin box.htb",
+        "Add api.box.htb and admin.box.htb as aliases of box.htb.",
+    ),
+    (
+        "hed expand box.htb vhosts.txt",
+        "Add the subdomains in a wordlist as aliases of box.htb.",
+    ),
+    (
+        "hed replace box.htb 10.10.10.6",
+        "Change the IP address of a host and its aliases.",
+    ),
+    (
+        "hed canonical box.htb",
+        "Make box.htb the name reverse lookups return for its address.",
+    ),
+    (
+        "hed rename box.htb target.htb",
+        "Rename a host, keeping its position and comments.",
+    ),
+    (
+        "hed move admin.box.htb 10.10.10.7",
+        "Move a name to another IP address.",
+    ),
+    (
+        "hed delete 10.10.10.6",
+        "Remove the entry for an IP address.",
+    ),
+    (
+        "hed check box.htb",
+        "Compare the hosts file to what the system resolver returns.",
+    ),
+    (
+        "hed who 10.10.10.0/24",
+        "List the names of the addresses in a network.",
+    ),
+    (
+        "cat hosts | hed --file - add box.htb 10.10.10.5 > new_hosts",
+        "Use hed as a filter.",
+    ),
+];
+
+/// Environment variables with what they set
+const ENVIRONMENT: &[(&str, &str)] = &[
+    (
+        "HED_CONFIG",
+        "Configuration file to use instead of the one of the user.",
+    ),
+    ("HED_FILE", "Hosts file to manage."),
+    ("HED_BACKUP_DIR", "Directory to store backups in."),
+    (
+        "HED_BACKUP_RETENTION",
+        "Number of backups to keep, 0 disables backups.",
+    ),
+    (
+        "HED_SECTION",
+        "Header comment of the section new entries are added to.",
+    ),
+    ("HED_STRICT", "Treat warnings as errors in verify and edit."),
+    (
+        "HED_ESCALATION",
+        "How privileges are obtained: sudo, doas, pkexec, run0 or none.",
+    ),
+    (
+        "HED_ALIGN",
+        "How the columns are aligned when writing: none, file or section.",
+    ),
+    ("HED_MAX_NAMES", "Maximum number of names on a line."),
+    (
+        "HED_MAX_LINE_LENGTH",
+        "Maximum number of characters on a line.",
+    ),
+    (
+        "HED_FLAVOR",
+        "Conventions of the hosts file: unix or windows.",
+    ),
+    (
+        "HED_LINE_ENDING",
+        "Line ending to write: lf or crlf, the one of the file when unset.",
+    ),
+    (
+        "HED_FINAL_NEWLINE",
+        "End the file with a line ending, as the file does when unset.",
+    ),
+    ("NO_COLOR", "Disable colors when set to a non-empty value."),
+    ("VISUAL, EDITOR", "Editor used by the edit command."),
+];
+
+/// Write the man page, rendered from the clap definition, to `out`
+pub fn render<W: Write>(out: &mut W) -> io::Result<()> {
+    let cmd = Cli::command();
+    let man = Man::new(cmd.clone());
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+
+    let mut roff = Roff::default();
+    roff.control("SH", ["OPTIONS"]);
+    options(&mut roff, &cmd);
+    commands(&mut roff, &cmd);
+
+    roff.control("SH", ["EXIT STATUS"]);
+    for (code, meaning) in exits::DOCUMENTED {
+        roff.control("TP", []);
+        roff.text([bold(code.to_string())]);
+        roff.text([roman(*meaning)]);
+    }
+
+    roff.control("SH", ["ENVIRONMENT"]);
+    definitions(&mut roff, ENVIRONMENT);
+
+    roff.control("SH", ["FILES"]);
+    definitions(
+        &mut roff,
+        &[
+            ("/etc/hosts", "The hosts file that is managed by default."),
+            ("/etc/hed.toml", "System wide configuration."),
+            (
+                "~/.config/hed/config.toml",
+                "Configuration of the user, in $XDG_CONFIG_HOME when it is set.",
+            ),
+        ],
+    );
+
+    roff.control("SH", ["EXAMPLES"]);
+    definitions(&mut roff, EXAMPLES);
+    roff.to_writer(out)?;
+
+    man.render_version_section(out)?;
+    man.render_authors_section(out)
+}
+
+/// A subsection for every command with its arguments
+fn commands(roff: &mut Roff, cmd: &Command) {
+    roff.control("SH", ["COMMANDS"]);
+    for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        let mut synopsis = vec![bold(format!("{} {}", cmd.get_name(), sub.get_name()))];
+        for arg in arguments(sub).filter(|a| a.is_positional()) {
+            synopsis.push(roman(" "));
+            synopsis.push(italic(value_name(arg)));
+        }
+        roff.control("SS", [sub.get_name()]);
+        roff.text(synopsis);
+        roff.control("PP", []);
+        if let Some(about) = sub.get_long_about().or_else(|| sub.get_about()) {
+            roff.text([roman(about.to_string())]);
+        }
+        options(roff, sub);
+    }
+}
+
+/// The arguments of `cmd` with their help
+fn options(roff: &mut Roff, cmd: &Command) {
+    for arg in arguments(cmd) {
+        let mut flag = vec![];
+        if let Some(long) = arg.get_long() {
+            flag.push(bold(format!("--{}", long)));
+            if arg.get_action().takes_values() {
+                flag.push(roman(" "));
+            }
+        }
+        if arg.is_positional() || arg.get_action().takes_values() {
+            flag.push(italic(value_name(arg)));
+        }
+        roff.control("TP", []);
+        roff.text(flag);
+        if let Some(help) = arg.get_long_help().or_else(|| arg.get_help()) {
+            roff.text([roman(help.to_string())]);
+        }
+    }
+}
+
+fn arguments(cmd: &Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments()
+        .filter(|a| !a.is_hide_set() && a.get_id() != "help" && a.get_id() != "version")
+}
+
+fn value_name(arg: &Arg) -> String {
+    match arg.get_value_names() {
+        Some(names) => names
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(" "),
+        None => arg.get_id().to_string().to_uppercase(),
+    }
+}
+
+fn definitions(roff: &mut Roff, items: &[(&str, &str)]) {
+    for (term, definition) in items {
+        roff.control("TP", []);
+        roff.text([bold(*term)]);
+        roff.text([roman(*definition)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::man::render;
+
+    #[test]
+    fn test_render() {
+        let mut out = vec![];
+        render(&mut out).unwrap();
+        let page = String::from_utf8(out).unwrap();
+        assert!(page.contains(".TH hed 1"));
+        assert!(page.contains("\\fB\\-\\-no\\-escalate\\fR\n"));
+        assert!(page.contains(".SS expand"));
+        assert!(page.contains("EXIT STATUS"));
+        assert!(!page.contains("write-helper"));
+        assert!(!page.contains("complete-hosts"));
+    }
+}
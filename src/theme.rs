@@ -0,0 +1,125 @@
+use termion::color;
+
+/// The colors used to print the parts of a host entry, stored as terminal
+/// escape sequences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub ip: String,
+    pub name: String,
+    pub alias: String,
+    pub comment: String,
+    /// Used for the numbers in status messages
+    pub highlight: String,
+    /// Used for errors and warnings in diagnostics
+    pub error: String,
+    pub warning: String,
+    pub reset: String,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            ip: color::Fg(color::Cyan).to_string(),
+            name: color::Fg(color::LightMagenta).to_string(),
+            alias: color::Fg(color::LightGreen).to_string(),
+            comment: color::Fg(color::LightBlue).to_string(),
+            highlight: color::Fg(color::Green).to_string(),
+            error: color::Fg(color::LightRed).to_string(),
+            warning: color::Fg(color::Yellow).to_string(),
+            reset: color::Fg(color::Reset).to_string(),
+        }
+    }
+}
+
+impl Theme {
+    /// A theme without any colors, for output that is not a terminal
+    pub fn plain() -> Theme {
+        Theme {
+            ip: String::new(),
+            name: String::new(),
+            alias: String::new(),
+            comment: String::new(),
+            highlight: String::new(),
+            error: String::new(),
+            warning: String::new(),
+            reset: String::new(),
+        }
+    }
+
+    /// Convert a color to its escape sequence. A color is a name (`red`,
+    /// `lightblue`, ...), an ANSI value (`0` - `255`) or `#rrggbb`.
+    pub fn color(color: &str) -> Option<String> {
+        let c = color.trim().to_lowercase();
+        let seq = match c.as_str() {
+            "black" => color::Fg(color::Black).to_string(),
+            "red" => color::Fg(color::Red).to_string(),
+            "green" => color::Fg(color::Green).to_string(),
+            "yellow" => color::Fg(color::Yellow).to_string(),
+            "blue" => color::Fg(color::Blue).to_string(),
+            "magenta" => color::Fg(color::Magenta).to_string(),
+            "cyan" => color::Fg(color::Cyan).to_string(),
+            "white" => color::Fg(color::White).to_string(),
+            "lightblack" => color::Fg(color::LightBlack).to_string(),
+            "lightred" => color::Fg(color::LightRed).to_string(),
+            "lightgreen" => color::Fg(color::LightGreen).to_string(),
+            "lightyellow" => color::Fg(color::LightYellow).to_string(),
+            "lightblue" => color::Fg(color::LightBlue).to_string(),
+            "lightmagenta" => color::Fg(color::LightMagenta).to_string(),
+            "lightcyan" => color::Fg(color::LightCyan).to_string(),
+            "lightwhite" => color::Fg(color::LightWhite).to_string(),
+            "reset" => color::Fg(color::Reset).to_string(),
+            _ => {
+                if let Some(hex) = c.strip_prefix('#') {
+                    // slicing below needs six ASCII characters
+                    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                        return None;
+                    }
+                    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+                    color::Fg(color::Rgb(channel(0)?, channel(2)?, channel(4)?)).to_string()
+                } else {
+                    color::Fg(color::AnsiValue(c.parse().ok()?)).to_string()
+                }
+            }
+        };
+        Some(seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::theme::Theme;
+    use crate::HostEntry;
+    use termion::color;
+
+    #[test]
+    fn test_color() {
+        assert_eq!(
+            Theme::color("LightBlue"),
+            Some(color::Fg(color::LightBlue).to_string())
+        );
+        assert_eq!(
+            Theme::color("208"),
+            Some(color::Fg(color::AnsiValue(208)).to_string())
+        );
+        assert_eq!(
+            Theme::color("#ff8000"),
+            Some(color::Fg(color::Rgb(255, 128, 0)).to_string())
+        );
+        assert_eq!(Theme::color("#ff80"), None);
+        assert_eq!(Theme::color("#aébbc"), None);
+        assert_eq!(Theme::color("#+f+f+f"), None);
+        assert_eq!(Theme::color("256"), None);
+        assert_eq!(Theme::color("purple"), None);
+    }
+
+    #[test]
+    fn test_plain() {
+        let entry: HostEntry = "10.10.10.10 box.htb www.box.htb # lab".parse().unwrap();
+        let mut out = vec![];
+        entry.color_print(&mut out, &Theme::plain()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "10.10.10.10\tbox.htb\twww.box.htb\t# lab\n"
+        );
+    }
+}
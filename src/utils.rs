@@ -1,18 +1,11 @@
 use std::{
-    fs::File,
-    io::{self, BufRead},
-    path::Path,
+    fmt,
+    io::{self, Write},
+    net::IpAddr,
+    ops::Range,
 };
 
-// The output is wrapped in a Result to allow matching on errors
-// Returns an Iterator to the Reader of the lines of the file.
-pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
-}
+use crate::theme::Theme;
 
 #[derive(Default, PartialEq, Debug)]
 pub struct Modifications {
@@ -30,9 +23,176 @@ impl Modifications {
         }
     }
 
-    pub(crate) fn merge(&mut self, m: Modifications) {
+    pub fn merge(&mut self, m: Modifications) {
         self.updated_entries += m.updated_entries;
         self.removed_entries += m.removed_entries;
         self.added_entries += m.added_entries;
     }
 }
+
+/// How serious a problem found while validating a hostfile is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found on a specific line (1-based) of the hostfile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+    /// The text on the line that causes the problem
+    pub text: Option<String>,
+}
+
+impl Issue {
+    pub fn error(line: usize, message: String) -> Issue {
+        Issue {
+            line,
+            severity: Severity::Error,
+            message,
+            text: None,
+        }
+    }
+
+    pub fn warning(line: usize, message: String) -> Issue {
+        Issue {
+            line,
+            severity: Severity::Warning,
+            message,
+            text: None,
+        }
+    }
+
+    /// Point the issue at `text` on its line
+    pub fn with_text(mut self, text: &str) -> Issue {
+        self.text = Some(String::from(text));
+        self
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "line {}: {}: {}", self.line, level, self.message)
+    }
+}
+
+/// A problem in a file with the line it was found on. `columns` is the span
+/// of the offending text in `source`, counted in characters from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub columns: Range<usize>,
+    pub source: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Print the diagnostic with the source line and the offending text
+    /// underlined, using the colors of `theme`
+    pub fn color_print<W: Write>(&self, f: &mut W, theme: &Theme) -> io::Result<()> {
+        let (level, color) = match self.severity {
+            Severity::Warning => ("warning", &theme.warning),
+            Severity::Error => ("error", &theme.error),
+        };
+        let gutter = self.line.to_string().len();
+        // tabs are shown as spaces to keep the underline aligned
+        let width = |s: &str| {
+            s.chars()
+                .map(|c| if c == '\t' { 4 } else { 1 })
+                .sum::<usize>()
+        };
+        let chars: Vec<char> = self.source.chars().collect();
+        let start = self.columns.start.min(chars.len());
+        let end = self.columns.end.clamp(start, chars.len());
+        let before: String = chars[..start].iter().collect();
+        let marked: String = chars[start..end].iter().collect();
+
+        writeln!(f, "{}{}{}: {}", color, level, theme.reset, self.message)?;
+        writeln!(
+            f,
+            "{:w$}--> {}:{}:{}",
+            "",
+            self.file,
+            self.line,
+            start + 1,
+            w = gutter
+        )?;
+        writeln!(f, "{:w$} |", "", w = gutter)?;
+        writeln!(f, "{} | {}", self.line, self.source.replace('\t', "    "))?;
+        writeln!(
+            f,
+            "{:w$} | {:p$}{}{}{}",
+            "",
+            "",
+            color,
+            "^".repeat(width(&marked).max(1)),
+            theme.reset,
+            w = gutter,
+            p = width(&before)
+        )
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = vec![];
+        self.color_print(&mut out, &Theme::plain())
+            .map_err(|_| fmt::Error)?;
+        write!(f, "{}", String::from_utf8_lossy(&out).trim_end())
+    }
+}
+
+/// A name on `line` that resolvers never return for that line, because the
+/// earlier line `by_line` has the name for the same address family.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shadowed {
+    pub line: usize,
+    pub name: String,
+    pub ip: IpAddr,
+    pub by_line: usize,
+    pub by_ip: IpAddr,
+}
+
+/// The names of an IP address, gathered from every line that has it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reverse {
+    pub ip: IpAddr,
+    /// The (1-based) numbers of the lines with the address
+    pub lines: Vec<usize>,
+    /// The names on these lines in the order resolvers read them, without
+    /// duplicates
+    pub names: Vec<String>,
+    /// The names of the first line, which a reverse lookup like
+    /// `getent hosts <ip>` returns: the canonical name and its aliases
+    pub answer: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{Diagnostic, Severity};
+
+    #[test]
+    fn test_diagnostic() {
+        let d = Diagnostic {
+            file: String::from("hosts"),
+            line: 12,
+            columns: 9..17,
+            source: String::from("10.0.0.1\tbad_name # lab"),
+            severity: Severity::Error,
+            message: String::from("malformed hostname `bad_name`"),
+        };
+        assert_eq!(
+            d.to_string(),
+            "error: malformed hostname `bad_name`\n  --> hosts:12:10\n   |\n12 | 10.0.0.1    bad_name # lab\n   |             ^^^^^^^^"
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::errors::ApplicationError;
+use crate::hostentry::HostEntry;
+
+/// Order of the host entries in `show`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// Numerically by IP address, IPv4 before IPv6
+    Ip,
+    /// Alphabetically by hostname
+    Name,
+}
+
+/// How the host entries are grouped in `show`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// By IPv4 /24 or IPv6 /64 network
+    Subnet,
+    /// By the last two labels of the hostname (`admin.box.htb` is in `box.htb`)
+    Domain,
+    /// By the comment line above the entries
+    HeaderComment,
+}
+
+/// The lines that are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Only {
+    Ipv4,
+    Ipv6,
+    Comments,
+    Entries,
+}
+
+/// How `HostFile::show_with` presents the hostfile. Without sorting or
+/// grouping the lines are shown in file order, otherwise only the host
+/// entries are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShowOptions {
+    pub sort: Option<SortBy>,
+    pub group_by: Option<GroupBy>,
+    pub only: Option<Only>,
+}
+
+/// A group of lines to show, with the title of the group if there is one
+pub type Group<'a> = (Option<String>, Vec<&'a HostEntry>);
+
+impl ShowOptions {
+    /// Select, group and sort the entries. Groups are in order of their
+    /// network or domain, or in file order for header comments.
+    pub fn groups<'a>(&self, entries: &'a [HostEntry]) -> Vec<Group<'a>> {
+        let reorder = self.sort.is_some() || self.group_by.is_some();
+        let selected: Vec<(usize, &HostEntry)> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| match self.only {
+                Some(Only::Ipv4) => matches!(e.ip, Some(ip) if ip.is_ipv4()),
+                Some(Only::Ipv6) => matches!(e.ip, Some(ip) if ip.is_ipv6()),
+                Some(Only::Comments) => e.ip.is_none() && e.comment.is_some(),
+                Some(Only::Entries) => e.ip.is_some(),
+                None => !reorder || e.ip.is_some(),
+            })
+            .collect();
+
+        let mut groups: Vec<Group> = vec![];
+        for (idx, entry) in selected {
+            let title = match self.group_by {
+                Some(GroupBy::Subnet) => entry.ip.map(subnet),
+                Some(GroupBy::Domain) => entry.name.as_deref().map(domain),
+                Some(GroupBy::HeaderComment) => header(entries, idx),
+                None => None,
+            };
+            match groups.iter_mut().find(|(t, _)| *t == title) {
+                Some((_, members)) => members.push(entry),
+                None => groups.push((title, vec![entry])),
+            }
+        }
+
+        match self.group_by {
+            // networks sort numerically, not by their title
+            Some(GroupBy::Subnet) => groups.sort_by_key(|(_, m)| m[0].ip.map(network)),
+            Some(GroupBy::Domain) => groups.sort_by(|(a, _), (b, _)| a.cmp(b)),
+            _ => {}
+        }
+        for (_, members) in groups.iter_mut() {
+            match self.sort {
+                Some(SortBy::Ip) => members.sort_by_key(|e| e.ip),
+                Some(SortBy::Name) => {
+                    members.sort_by_key(|e| e.name.as_deref().map(str::to_lowercase))
+                }
+                None => {}
+            }
+        }
+        groups
+    }
+}
+
+/// An IP address, or a network in CIDR notation: `10.10.10.0/24`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+    pub ip: IpAddr,
+    pub prefix: u8,
+}
+
+impl Network {
+    /// Indicate if `ip` is in the network
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.ip, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix) = match s.split_once('/') {
+            Some((ip, prefix)) => (ip, Some(prefix)),
+            None => (s, None),
+        };
+        let ip: IpAddr = ip
+            .parse()
+            .map_err(|_| ApplicationError::IpAddressConversion())?;
+        let max = if ip.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => match p.parse() {
+                Ok(p) if p <= max => p,
+                _ => return Err(ApplicationError::IpAddressConversion()),
+            },
+            None => max,
+        };
+        Ok(Network { ip, prefix })
+    }
+}
+
+/// The IPv4 /24 or IPv6 /64 network of `ip`
+fn network(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            IpAddr::from([o[0], o[1], o[2], 0])
+        }
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            IpAddr::from([s[0], s[1], s[2], s[3], 0, 0, 0, 0])
+        }
+    }
+}
+
+fn subnet(ip: IpAddr) -> String {
+    let prefix = if ip.is_ipv4() { 24 } else { 64 };
+    format!("{}/{}", network(ip), prefix)
+}
+
+/// The last two labels of `name`
+fn domain(name: &str) -> String {
+    let labels: Vec<&str> = name.trim_end_matches('.').rsplitn(3, '.').collect();
+    match labels.as_slice() {
+        [tld, sld, ..] => format!("{}.{}", sld, tld),
+        _ => String::from(name),
+    }
+}
+
+/// The closest comment line above the entry at `idx`
+fn header(entries: &[HostEntry], idx: usize) -> Option<String> {
+    entries[..idx]
+        .iter()
+        .rev()
+        .find(|e| e.ip.is_none() && e.comment.is_some())
+        .and_then(|e| e.comment.clone())
+}
+
+impl FromStr for SortBy {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ip" => Ok(SortBy::Ip),
+            "name" => Ok(SortBy::Name),
+            _ => Err(ApplicationError::UnknownOption(format!(
+                "sort by `{}`, use ip or name",
+                s
+            ))),
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "subnet" => Ok(GroupBy::Subnet),
+            "domain" => Ok(GroupBy::Domain),
+            "header-comment" => Ok(GroupBy::HeaderComment),
+            _ => Err(ApplicationError::UnknownOption(format!(
+                "group by `{}`, use subnet, domain or header-comment",
+                s
+            ))),
+        }
+    }
+}
+
+impl FromStr for Only {
+    type Err = ApplicationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ipv4" => Ok(Only::Ipv4),
+            "ipv6" => Ok(Only::Ipv6),
+            "comments" => Ok(Only::Comments),
+            "entries" => Ok(Only::Entries),
+            _ => Err(ApplicationError::UnknownOption(format!(
+                "only `{}`, use ipv4, ipv6, comments or entries",
+                s
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::view::{domain, GroupBy, Network, Only, ShowOptions, SortBy};
+    use crate::HostEntry;
+
+    fn entries(lines: &[&str]) -> Vec<HostEntry> {
+        lines.iter().map(|l| l.parse().unwrap()).collect()
+    }
+
+    fn ips(group: &[&HostEntry]) -> Vec<String> {
+        group.iter().map(|e| e.ip.unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_sort() {
+        let e = entries(&[
+            "# lab",
+            "10.10.10.100 b.htb",
+            "::1 localhost",
+            "10.10.10.9 a.htb",
+            "",
+            "10.10.2.1 c.htb",
+        ]);
+        let plain = ShowOptions::default().groups(&e);
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].1.len(), 6);
+
+        let options = ShowOptions {
+            sort: Some(SortBy::Ip),
+            ..Default::default()
+        };
+        let groups = options.groups(&e);
+        assert_eq!(
+            ips(&groups[0].1),
+            vec!["10.10.2.1", "10.10.10.9", "10.10.10.100", "::1"]
+        );
+
+        let options = ShowOptions {
+            sort: Some(SortBy::Name),
+            only: Some(Only::Ipv4),
+            ..Default::default()
+        };
+        let groups = options.groups(&e);
+        assert_eq!(
+            ips(&groups[0].1),
+            vec!["10.10.10.9", "10.10.10.100", "10.10.2.1"]
+        );
+
+        let options = ShowOptions {
+            only: Some(Only::Comments),
+            ..Default::default()
+        };
+        assert_eq!(options.groups(&e)[0].1.len(), 1);
+    }
+
+    #[test]
+    fn test_group_by() {
+        let e = entries(&[
+            "# boxes",
+            "10.10.10.100 admin.b.htb",
+            "10.10.2.1 c.htb",
+            "# more",
+            "10.10.10.9 b.htb",
+        ]);
+        let options = ShowOptions {
+            group_by: Some(GroupBy::Subnet),
+            sort: Some(SortBy::Ip),
+            ..Default::default()
+        };
+        let groups = options.groups(&e);
+        assert_eq!(groups[0].0.as_deref(), Some("10.10.2.0/24"));
+        assert_eq!(groups[1].0.as_deref(), Some("10.10.10.0/24"));
+        assert_eq!(ips(&groups[1].1), vec!["10.10.10.9", "10.10.10.100"]);
+
+        let options = ShowOptions {
+            group_by: Some(GroupBy::Domain),
+            ..Default::default()
+        };
+        let groups = options.groups(&e);
+        assert_eq!(groups[0].0.as_deref(), Some("b.htb"));
+        assert_eq!(ips(&groups[0].1), vec!["10.10.10.100", "10.10.10.9"]);
+
+        let options = ShowOptions {
+            group_by: Some(GroupBy::HeaderComment),
+            ..Default::default()
+        };
+        let groups = options.groups(&e);
+        assert_eq!(groups[0].0.as_deref(), Some("boxes"));
+        assert_eq!(groups[1].0.as_deref(), Some("more"));
+
+        assert_eq!(domain("localhost"), "localhost");
+        assert_eq!(domain("a.b.htb"), "b.htb");
+    }
+
+    #[test]
+    fn test_network() {
+        let net: Network = "10.10.10.0/24".parse().unwrap();
+        assert!(net.contains(&"10.10.10.200".parse().unwrap()));
+        assert!(!net.contains(&"10.10.11.1".parse().unwrap()));
+        assert!(!net.contains(&"::1".parse().unwrap()));
+
+        let ip: Network = "fd00::5".parse().unwrap();
+        assert_eq!(ip.prefix, 128);
+        assert!(ip.contains(&"fd00::5".parse().unwrap()));
+        assert!(!ip.contains(&"fd00::6".parse().unwrap()));
+
+        let all: Network = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains(&"192.168.1.1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<Network>().is_err());
+        assert!("box.htb".parse::<Network>().is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use hed::{view::Network, ApplicationError, HostFile, Modifications, Reverse, Theme};
+
+/// List the names of the IP address, or of every address in the network,
+/// `query` and what a reverse lookup returns for them.
+pub fn who(hf: &HostFile, query: &str, theme: &Theme) -> Result<Modifications, ApplicationError> {
+    let network: Network = query.parse()?;
+    let found = hf.who(&network);
+    if found.is_empty() {
+        return Err(ApplicationError::AddressNotFound(String::from(query)));
+    }
+    for (idx, reverse) in found.iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+        for line in report(reverse, theme) {
+            println!("{}", line);
+        }
+    }
+    Ok(Modifications::new())
+}
+
+/// The lines that describe an address: where it is, all of its names and the
+/// answer of `getent hosts`, the first name of which is the canonical name.
+fn report(reverse: &Reverse, theme: &Theme) -> Vec<String> {
+    let lines: Vec<String> = reverse.lines.iter().map(|l| l.to_string()).collect();
+    let canonical = reverse.answer.first().map_or("", String::as_str);
+    vec![
+        format!(
+            "{}{}{} (line{} {})",
+            theme.ip,
+            reverse.ip,
+            theme.reset,
+            if lines.len() == 1 { "" } else { "s" },
+            lines.join(", ")
+        ),
+        format!("  canonical: {}{}{}", theme.name, canonical, theme.reset),
+        format!(
+            "  names:     {}{}{}",
+            theme.alias,
+            reverse.names.join(" "),
+            theme.reset
+        ),
+        format!(
+            "  getent:    {:<15} {}",
+            reverse.ip.to_string(),
+            reverse.answer.join(" ")
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use hed::{view::Network, HostFile, Theme};
+
+    use crate::who::report;
+
+    #[test]
+    fn test_report() {
+        let mut hf = HostFile::new("hosts");
+        hf.parse_from(
+            "10.10.10.5 box.htb www.box.htb\n10.10.10.6 other.htb\n10.10.10.5 api.box.htb\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let found = hf.who(&"10.10.10.5".parse::<Network>().unwrap());
+        assert_eq!(
+            report(&found[0], &Theme::plain()),
+            vec![
+                "10.10.10.5 (lines 1, 3)",
+                "  canonical: box.htb",
+                "  names:     box.htb www.box.htb api.box.htb",
+                "  getent:    10.10.10.5      box.htb www.box.htb",
+            ]
+        );
+        let found = hf.who(&"10.10.10.6".parse::<Network>().unwrap());
+        assert_eq!(report(&found[0], &Theme::plain())[0], "10.10.10.6 (line 2)");
+    }
+}